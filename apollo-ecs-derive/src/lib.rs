@@ -0,0 +1,166 @@
+//! Derive macros for apollo-ecs. These are re-exported by `apollo_ecs` and
+//! shouldn't need to be depended on directly.
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+/// Implements `apollo_ecs::Bundle` for a struct with named fields, adding each
+/// field to the entity as a component. Fields marked `#[bundle(skip)]` are
+/// dropped instead.
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let item = match parse_struct(input) {
+        Ok(item) => item,
+        Err(message) => return compile_error(message)
+    };
+
+    let fields = match item.fields {
+        Some(fields) => fields,
+        None => return compile_error("Bundle can only be derived for structs with named fields")
+    };
+
+    let mut body = String::new();
+    for field in fields.iter().filter(|f| !f.skip) {
+        body.push_str(&format!("world.add_component(ent, self.{});\n", field.name));
+    }
+
+    format!(
+        "impl ::apollo_ecs::Bundle for {} {{
+            fn insert_into(self, world: &::apollo_ecs::World, ent: ::apollo_ecs::Entity) {{
+                {}
+            }}
+        }}",
+        item.name, body
+    ).parse().unwrap()
+}
+
+struct Struct {
+    name: String,
+    /// The struct's named fields, or `None` for tuple and unit structs
+    fields: Option<Vec<Field>>
+}
+
+struct Field {
+    name: String,
+    skip: bool
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("compile_error!({:?});", message).parse().unwrap()
+}
+
+/// Pulls the name and fields out of a struct definition
+fn parse_struct(input: TokenStream) -> Result<Struct, &'static str> {
+    let mut tokens = input.into_iter().peekable();
+
+    // Skip over attributes and visibility to the `struct` keyword
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Ident(ref ident)) if ident.to_string() == "struct" => break,
+            Some(TokenTree::Ident(ref ident)) if ident.to_string() == "enum" || ident.to_string() == "union" => {
+                return Err("derive is only supported on structs");
+            },
+            Some(_) => (),
+            None => return Err("expected a struct")
+        }
+    }
+
+    let name = match tokens.next() {
+        Some(TokenTree::Ident(ident)) => ident.to_string(),
+        _ => return Err("expected a struct name")
+    };
+
+    match tokens.next() {
+        Some(TokenTree::Group(ref group)) if group.delimiter() == Delimiter::Brace => Ok(Struct {
+            name,
+            fields: Some(parse_fields(group.stream()))
+        }),
+        Some(TokenTree::Punct(ref punct)) if punct.as_char() == '<' => {
+            Err("derive is not supported on generic structs")
+        },
+        _ => Ok(Struct {
+            name,
+            fields: None
+        })
+    }
+}
+
+/// Splits the body of a struct with named fields into its fields
+fn parse_fields(body: TokenStream) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut field = Vec::new();
+    // Commas inside a field's type only appear between angle brackets
+    let mut depth = 0;
+    let mut prev_dash = false;
+
+    for token in body {
+        match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == ',' && depth == 0 => {
+                fields.extend(parse_field(&field));
+                field.clear();
+                continue;
+            },
+            TokenTree::Punct(ref punct) if punct.as_char() == '<' => depth += 1,
+            // `->` in a function pointer type doesn't close a bracket
+            TokenTree::Punct(ref punct) if punct.as_char() == '>' && !prev_dash => depth -= 1,
+            _ => ()
+        }
+
+        prev_dash = match token {
+            TokenTree::Punct(ref punct) => punct.as_char() == '-',
+            _ => false
+        };
+        field.push(token);
+    }
+    fields.extend(parse_field(&field));
+
+    fields
+}
+
+/// Reads the name of a single field, and whether it's marked `#[bundle(skip)]`
+fn parse_field(tokens: &[TokenTree]) -> Option<Field> {
+    let mut skip = false;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match tokens[i] {
+            TokenTree::Punct(ref punct) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(ref attr)) = tokens.get(i + 1) {
+                    skip |= is_skip(attr.stream());
+                }
+                i += 2;
+            },
+            TokenTree::Ident(ref ident) if ident.to_string() == "pub" => {
+                i += 1;
+                // `pub(crate)` and friends
+                if let Some(TokenTree::Group(ref group)) = tokens.get(i) {
+                    if group.delimiter() == Delimiter::Parenthesis {
+                        i += 1;
+                    }
+                }
+            },
+            TokenTree::Ident(ref ident) => return Some(Field {
+                name: ident.to_string(),
+                skip
+            }),
+            _ => i += 1
+        }
+    }
+
+    None
+}
+
+/// Whether an attribute's contents are `bundle(skip)`
+fn is_skip(attr: TokenStream) -> bool {
+    let mut tokens = attr.into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(ref ident)), Some(TokenTree::Group(ref args))) if ident.to_string() == "bundle" => {
+            args.stream().into_iter().any(|arg| match arg {
+                TokenTree::Ident(ref ident) => ident.to_string() == "skip",
+                _ => false
+            })
+        },
+        _ => false
+    }
+}
@@ -11,10 +11,11 @@ use cpuprofiler::PROFILER;
 extern crate apollo_ecs;
 
 use test::Bencher;
+use std::any::TypeId;
 
 
 use apollo_ecs::*;
-use apollo_ecs::systems::IterativeSystem;
+use apollo_ecs::systems::{IterativeSystem, ParallelSystem};
 
 struct TestSystem1;
 struct TestSystem2;
@@ -31,7 +32,7 @@ impl IterativeSystem for TestSystem1 {
     }
 
     fn process(&mut self, ent: Entity, world: &World) {
-        let pos = world.get_component::<Position>(ent).unwrap();
+        let mut pos = world.get_component::<Position>(ent).unwrap();
 
         pos.x += 1.0;
         pos.y -= 1.0;
@@ -44,7 +45,7 @@ impl IterativeSystem for TestSystem2 {
     }
 
     fn process(&mut self, ent: Entity, world: &World) {
-        let pos = world.get_component::<Position>(ent).unwrap();
+        let mut pos = world.get_component::<Position>(ent).unwrap();
 
         pos.x -= 1.0;
         pos.y += 1.0;
@@ -57,7 +58,7 @@ impl IterativeSystem for TestSystem3 {
     }
 
     fn process(&mut self, ent: Entity, world: &World) {
-        let pos = world.get_component::<Position>(ent).unwrap();
+        let mut pos = world.get_component::<Position>(ent).unwrap();
 
         pos.x += 1.0;
         pos.y -= 1.0;
@@ -92,4 +93,185 @@ fn bench_16384_ents(b: &mut Bencher) {
     {
         PROFILER.lock().unwrap().stop().unwrap();
     }
-}
\ No newline at end of file
+}
+
+#[bench]
+fn bench_16384_ents_dense(b: &mut Bencher) {
+    let mut world = World::new();
+    world.register_dense_component::<Position>();
+    world.register_iterative_system(TestSystem1);
+    world.register_iterative_system(TestSystem2);
+    world.register_iterative_system(TestSystem3);
+
+    for _ in 0..16384 {
+        let ent = world.create_entity();
+
+        world.add_component(ent, Position {
+            x: 0.0,
+            y: 0.0
+        });
+    }
+
+    b.iter(|| {
+        world.process()
+    });
+}
+
+struct Unused;
+
+struct EmptySystem;
+
+impl IterativeSystem for EmptySystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Unused>())
+    }
+
+    fn process(&mut self, _ent: Entity, _world: &World) {}
+}
+
+#[bench]
+fn bench_16384_ents_64_empty_systems(b: &mut Bencher) {
+    let mut world = World::new();
+    world.register_iterative_system(TestSystem1);
+    for _ in 0..64 {
+        world.register_iterative_system(EmptySystem);
+    }
+
+    for _ in 0..16384 {
+        let ent = world.create_entity();
+
+        world.add_component(ent, Position {
+            x: 0.0,
+            y: 0.0
+        });
+    }
+
+    b.iter(|| {
+        world.process()
+    });
+}
+
+struct Velocity {
+    x: f32,
+    y: f32
+}
+
+struct Health(f32);
+
+struct MoveSystem;
+struct RegenSystem;
+
+impl IterativeSystem for MoveSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Position>().with::<Velocity>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        let vel = world.get_component_ref::<Velocity>(ent).unwrap();
+        let mut pos = world.get_component::<Position>(ent).unwrap();
+
+        pos.x += vel.x;
+        pos.y += vel.y;
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec!(TypeId::of::<Velocity>())
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Position>()))
+    }
+}
+
+impl IterativeSystem for RegenSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Health>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        let mut health = world.get_component::<Health>(ent).unwrap();
+
+        health.0 = (health.0 + 0.5).min(100.0);
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Health>()))
+    }
+}
+
+fn disjoint_world() -> World {
+    let mut world = World::new();
+    world.register_iterative_system(MoveSystem);
+    world.register_iterative_system(RegenSystem);
+
+    for _ in 0..16384 {
+        let ent = world.create_entity();
+
+        world.add_component(ent, Position { x: 0.0, y: 0.0 });
+        world.add_component(ent, Velocity { x: 1.0, y: -1.0 });
+        world.add_component(ent, Health(0.0));
+    }
+
+    world
+}
+
+#[bench]
+fn bench_16384_ents_disjoint(b: &mut Bencher) {
+    let mut world = disjoint_world();
+
+    b.iter(|| {
+        world.process()
+    });
+}
+
+#[cfg(feature = "parallel")]
+#[bench]
+fn bench_16384_ents_disjoint_parallel(b: &mut Bencher) {
+    let mut world = disjoint_world();
+
+    b.iter(|| {
+        unsafe { world.process_parallel() }
+    });
+}
+
+struct ParallelMoveSystem;
+
+impl ParallelSystem for ParallelMoveSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Position>().with::<Velocity>())
+    }
+
+    fn process(&self, ent: Entity, world: &World) {
+        let vel = world.get_component_ref::<Velocity>(ent).unwrap();
+        let mut pos = world.get_component::<Position>(ent).unwrap();
+
+        pos.x += vel.x;
+        pos.y += vel.y;
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec!(TypeId::of::<Velocity>())
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Position>()))
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[bench]
+fn bench_16384_ents_parallel_system(b: &mut Bencher) {
+    let mut world = World::new();
+    world.register_parallel_system(ParallelMoveSystem);
+
+    for _ in 0..16384 {
+        let ent = world.create_entity();
+
+        world.add_component(ent, Position { x: 0.0, y: 0.0 });
+        world.add_component(ent, Velocity { x: 1.0, y: -1.0 });
+    }
+
+    b.iter(|| {
+        unsafe { world.process_parallel() }
+    });
+}
@@ -0,0 +1,21 @@
+//! Checks that apollo-ecs builds for `no_std` targets. Build with
+//! `cargo build --manifest-path ci/no-std-check/Cargo.toml`.
+#![no_std]
+
+extern crate apollo_ecs;
+
+use apollo_ecs::{Entity, Matchers, World};
+
+pub struct Position(pub f32);
+
+pub fn spawn(world: &mut World) -> Entity {
+    let ent = world.create_entity();
+    world.add_component(ent, Position(0.0));
+    world.process();
+
+    ent
+}
+
+pub fn count(world: &World) -> usize {
+    world.count_matching(Matchers::with::<Position>())
+}
@@ -0,0 +1,138 @@
+use super::Entity;
+use super::world::Components;
+
+use super::prelude::any::TypeId;
+use super::prelude::{HashMap, Vec};
+
+/// A group of entities that all hold exactly the same set of
+/// component types.
+pub struct Archetype {
+    pub signature: Vec<TypeId>,
+    pub entities: Vec<Entity>
+}
+
+/// Index of every archetype present in a world, along with the
+/// archetype each live entity currently belongs to.
+pub struct Archetypes {
+    archetypes: Vec<Archetype>,
+    index: HashMap<Vec<TypeId>, usize>,
+    locations: Vec<Option<(usize, usize)>>
+}
+
+impl Archetypes {
+    pub fn new() -> Archetypes {
+        Archetypes {
+            archetypes: Vec::new(),
+            index: HashMap::new(),
+            locations: Vec::new()
+        }
+    }
+
+    /// The number of distinct archetypes seen so far. Archetypes are never
+    /// removed, so ids below this value stay stable for the life of the world.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.archetypes.len()
+    }
+
+    #[inline]
+    pub fn get(&self, id: usize) -> &Archetype {
+        &self.archetypes[id]
+    }
+
+    /// Places `ent` into the archetype matching `components`, moving it out
+    /// of its previous archetype if its layout changed.
+    pub fn relocate(&mut self, ent: Entity, components: &Components) {
+        let signature = signature_of(components);
+
+        if let Some(Some((id, _))) = self.locations.get(ent) {
+            if self.archetypes[*id].signature == signature {
+                return;
+            }
+        }
+
+        self.remove(ent);
+
+        let id = match self.index.get(&signature) {
+            Some(&id) => id,
+            None => {
+                let id = self.archetypes.len();
+                self.index.insert(signature.clone(), id);
+                self.archetypes.push(Archetype {
+                    signature,
+                    entities: Vec::new()
+                });
+
+                id
+            }
+        };
+
+        let row = self.archetypes[id].entities.len();
+        self.archetypes[id].entities.push(ent);
+
+        if self.locations.len() <= ent {
+            self.locations.resize(ent + 1, None);
+        }
+        self.locations[ent] = Some((id, row));
+    }
+
+    /// Removes every entity from its archetype. The archetypes themselves are
+    /// kept, so their ids stay valid.
+    pub fn clear_entities(&mut self) {
+        for archetype in self.archetypes.iter_mut() {
+            archetype.entities.clear();
+        }
+        self.locations.clear();
+    }
+
+    /// Removes `ent` from whichever archetype currently holds it.
+    pub fn remove(&mut self, ent: Entity) {
+        if let Some((id, row)) = self.locations.get_mut(ent).and_then(|l| l.take()) {
+            let entities = &mut self.archetypes[id].entities;
+            entities.swap_remove(row);
+
+            if let Some(&moved) = entities.get(row) {
+                self.locations[moved] = Some((id, row));
+            }
+        }
+    }
+}
+
+/// Sorted, de-duplicated list of the component types in `components`
+fn signature_of(components: &Components) -> Vec<TypeId> {
+    let mut signature: Vec<TypeId> = components.iter().map(|&(ty, _)| ty).collect();
+    signature.sort();
+    signature.dedup();
+
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::any::Any;
+
+    #[test]
+    fn test_relocate() {
+        struct A;
+        struct B;
+
+        let mut archetypes = Archetypes::new();
+        archetypes.relocate(0, &Components::from(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any))));
+        archetypes.relocate(1, &Components::from(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any))));
+        archetypes.relocate(2, &Components::from(vec!((TypeId::of::<B>(), &mut 1 as *mut dyn Any), (TypeId::of::<A>(), &mut 2 as *mut dyn Any))));
+
+        assert_eq!(archetypes.len(), 2);
+        assert_eq!(archetypes.get(0).entities, vec!(0, 1));
+        assert_eq!(archetypes.get(1).entities, vec!(2));
+
+        archetypes.relocate(0, &Components::from(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any), (TypeId::of::<B>(), &mut 2 as *mut dyn Any))));
+
+        assert_eq!(archetypes.len(), 2);
+        assert_eq!(archetypes.get(0).entities, vec!(1));
+        assert_eq!(archetypes.get(1).entities, vec!(2, 0));
+
+        archetypes.remove(2);
+        assert_eq!(archetypes.get(1).entities, vec!(0));
+    }
+}
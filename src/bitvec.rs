@@ -1,3 +1,6 @@
+use super::prelude::Vec;
+
+#[derive(Clone)]
 pub struct BitVec {
     data: Vec<u32>,
     nbits: usize
@@ -11,7 +14,31 @@ impl BitVec {
 
     pub fn ones(bits: usize) -> BitVec {
         let words = u32s(bits);
-        BitVec { data: vec![1; words], nbits: words * 32 }
+        BitVec { data: vec![!0; words], nbits: words * 32 }
+    }
+
+    /// Number of bits the vector holds, a multiple of 32
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nbits
+    }
+
+    /// Makes room for at least `bits` bits, leaving the new ones unset
+    pub fn grow(&mut self, bits: usize) {
+        if bits > self.nbits {
+            self.data.resize(u32s(bits), 0);
+            self.nbits = self.data.len() * 32;
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, bit: usize) -> bool {
+        assert!(bit < self.nbits);
+
+        let word = bit / 32;
+        let b = bit % 32;
+
+        self.data[word] & (1 << b) != 0
     }
 
     #[inline]
@@ -22,7 +49,7 @@ impl BitVec {
         let b = bit % 32;
         let flag = 1 << b;
 
-        self.data[word] = self.data[word] | flag;
+        self.data[word] |= flag;
     }
 
     #[inline]
@@ -33,7 +60,7 @@ impl BitVec {
         let b = bit % 32;
         let flag = 1 << b;
 
-        self.data[word] = self.data[word] & !flag;
+        self.data[word] &= !flag;
     }
 
     #[inline]
@@ -83,13 +110,160 @@ impl BitVec {
 
         true
     }
+
+    /// Number of bits set in this vector
+    pub fn count_ones(&self) -> usize {
+        self.data.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Bits set in both `self` and `other`
+    pub fn and(&self, other: &BitVec) -> BitVec {
+        self.combine(other, |a, b| a & b)
+    }
+
+    /// Bits set in either `self` or `other`
+    pub fn or(&self, other: &BitVec) -> BitVec {
+        self.combine(other, |a, b| a | b)
+    }
+
+    /// Bits set in exactly one of `self` and `other`
+    pub fn xor(&self, other: &BitVec) -> BitVec {
+        self.combine(other, |a, b| a ^ b)
+    }
+
+    /// Bits not set in `self`
+    pub fn not(&self) -> BitVec {
+        BitVec {
+            data: self.data.iter().map(|word| !word).collect(),
+            nbits: self.nbits
+        }
+    }
+
+    /// Iterates the indices of the set bits in ascending order, skipping over
+    /// empty words entirely.
+    pub fn iter_ones(&self) -> Ones<'_> {
+        Ones {
+            data: &self.data,
+            word: 0,
+            current: self.data.first().cloned().unwrap_or(0)
+        }
+    }
+
+    #[inline]
+    fn combine<F>(&self, other: &BitVec, f: F) -> BitVec where F: Fn(u32, u32) -> u32 {
+        assert_eq!(self.nbits, other.nbits);
+
+        BitVec {
+            data: self.data.iter().zip(other.data.iter()).map(|(&a, &b)| f(a, b)).collect(),
+            nbits: self.nbits
+        }
+    }
+}
+
+/// Iterator over the indices of the set bits in a `BitVec`
+pub struct Ones<'a> {
+    data: &'a [u32],
+    word: usize,
+    current: u32
+}
+
+impl<'a> Iterator for Ones<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.word += 1;
+            if self.word >= self.data.len() {
+                return None;
+            }
+
+            self.current = self.data[self.word];
+        }
+
+        let b = self.current.trailing_zeros() as usize;
+        // Clear the lowest set bit
+        self.current &= self.current - 1;
+
+        Some(self.word * 32 + b)
+    }
 }
 
 #[inline]
 fn u32s(bits: usize) -> usize {
-    if bits % 32 == 0 {
-        bits / 32
-    } else {
-        bits / 32 + 1
+    bits.div_ceil(32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(nbits: usize, set: &[usize]) -> BitVec {
+        let mut vec = BitVec::new(nbits);
+        for &bit in set {
+            vec.set(bit);
+        }
+
+        vec
+    }
+
+    #[test]
+    fn test_count_ones() {
+        assert_eq!(BitVec::new(64).count_ones(), 0);
+        assert_eq!(BitVec::ones(64).count_ones(), 64);
+        assert_eq!(bits(64, &[0, 31, 32, 63]).count_ones(), 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_combinators() {
+        let a = bits(64, &[0, 31, 32]);
+        let b = bits(64, &[31, 32, 63]);
+
+        let and = a.and(&b);
+        assert_eq!(and.count_ones(), 2);
+        assert!(and.get(31) && and.get(32));
+
+        let or = a.or(&b);
+        assert_eq!(or.count_ones(), 4);
+        assert!(or.get(0) && or.get(31) && or.get(32) && or.get(63));
+
+        let xor = a.xor(&b);
+        assert_eq!(xor.count_ones(), 2);
+        assert!(xor.get(0) && xor.get(63));
+        assert!(!xor.get(31) && !xor.get(32));
+
+        let not = a.not();
+        assert_eq!(not.count_ones(), 61);
+        assert!(!not.get(31) && !not.get(32));
+        assert!(not.get(30) && not.get(33));
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut vec = bits(32, &[31]);
+        vec.grow(33);
+        assert_eq!(vec.len(), 64);
+        assert!(vec.get(31) && !vec.get(32));
+
+        vec.grow(10);
+        assert_eq!(vec.len(), 64);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mismatched_lengths() {
+        BitVec::new(32).and(&BitVec::new(64));
+    }
+
+    #[test]
+    fn test_iter_ones() {
+        let vec = bits(128, &[0, 33, 100]);
+        assert_eq!(vec.iter_ones().collect::<Vec<usize>>(), vec!(0, 33, 100));
+
+        let vec = bits(128, &[31, 32, 127]);
+        assert_eq!(vec.iter_ones().collect::<Vec<usize>>(), vec!(31, 32, 127));
+
+        assert_eq!(BitVec::new(128).iter_ones().count(), 0);
+        assert_eq!(BitVec::new(0).iter_ones().count(), 0);
+        assert_eq!(BitVec::ones(64).iter_ones().count(), 64);
+    }
+}
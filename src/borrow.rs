@@ -0,0 +1,409 @@
+use super::Entity;
+use super::world::Components;
+
+use super::prelude::any::{Any, TypeId};
+#[cfg(not(feature = "parallel"))]
+use super::prelude::cell::Cell;
+use super::prelude::cell::RefCell;
+use super::prelude::fmt;
+use super::prelude::marker::PhantomData;
+use super::prelude::ops::{Deref, DerefMut};
+use super::prelude::Vec;
+#[cfg(feature = "parallel")]
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+#[cfg(feature = "parallel")]
+use std::sync::Mutex;
+
+const UNUSED: isize = 0;
+const WRITING: isize = -1;
+
+/// Tracks the outstanding borrows of a single component the way a `RefCell`
+/// does: positive values count shared borrows, and `WRITING` marks a mutable
+/// one. With the `parallel` feature enabled the flag is atomic, so systems run
+/// by `process_parallel` can borrow components of the same entity at once.
+#[cfg(not(feature = "parallel"))]
+pub struct BorrowFlag(Cell<isize>);
+
+#[cfg(feature = "parallel")]
+pub struct BorrowFlag(AtomicIsize);
+
+#[cfg(not(feature = "parallel"))]
+impl BorrowFlag {
+    pub fn new() -> BorrowFlag {
+        BorrowFlag(Cell::new(UNUSED))
+    }
+
+    #[inline]
+    pub fn is_borrowed(&self) -> bool {
+        self.0.get() != UNUSED
+    }
+
+    #[inline]
+    fn borrow(&self) -> bool {
+        let current = self.0.get();
+        if current == WRITING {
+            return false;
+        }
+
+        self.0.set(current + 1);
+        true
+    }
+
+    #[inline]
+    fn borrow_mut(&self) -> bool {
+        if self.0.get() != UNUSED {
+            return false;
+        }
+
+        self.0.set(WRITING);
+        true
+    }
+
+    #[inline]
+    fn release(&self) {
+        self.0.set(self.0.get() - 1);
+    }
+
+    #[inline]
+    fn release_mut(&self) {
+        self.0.set(UNUSED);
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl BorrowFlag {
+    pub fn new() -> BorrowFlag {
+        BorrowFlag(AtomicIsize::new(UNUSED))
+    }
+
+    #[inline]
+    pub fn is_borrowed(&self) -> bool {
+        self.0.load(Ordering::Acquire) != UNUSED
+    }
+
+    fn borrow(&self) -> bool {
+        let mut current = self.0.load(Ordering::Relaxed);
+        loop {
+            if current == WRITING {
+                return false;
+            }
+
+            match self.0.compare_exchange_weak(current, current + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(actual) => current = actual
+            }
+        }
+    }
+
+    fn borrow_mut(&self) -> bool {
+        self.0.compare_exchange(UNUSED, WRITING, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    fn release(&self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+
+    fn release_mut(&self) {
+        self.0.store(UNUSED, Ordering::Release);
+    }
+}
+
+impl Default for BorrowFlag {
+    fn default() -> BorrowFlag {
+        BorrowFlag::new()
+    }
+}
+
+/// Whether a component was added or mutably borrowed during the current tick.
+/// Atomic with the `parallel` feature enabled, like `BorrowFlag`.
+#[cfg(not(feature = "parallel"))]
+#[derive(Default)]
+pub struct ChangeFlag(Cell<bool>);
+
+#[cfg(feature = "parallel")]
+#[derive(Default)]
+pub struct ChangeFlag(AtomicBool);
+
+impl ChangeFlag {
+    /// A flag for a component that has just been added, and so has changed
+    pub fn new() -> ChangeFlag {
+        let flag = ChangeFlag::default();
+        flag.set();
+
+        flag
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    #[inline]
+    pub fn get(&self) -> bool {
+        self.0.get()
+    }
+
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    #[inline]
+    pub fn set(&self) {
+        self.0.set(true);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn set(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Sets the flag, returning whether it was already set
+    #[cfg(not(feature = "parallel"))]
+    #[inline]
+    pub fn test_and_set(&self) -> bool {
+        self.0.replace(true)
+    }
+
+    #[cfg(feature = "parallel")]
+    #[inline]
+    pub fn test_and_set(&self) -> bool {
+        self.0.swap(true, Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        *self.0.get_mut() = false;
+    }
+}
+
+/// The entities with components marked as changed during the current tick,
+/// whose flags are cleared at its end. Locked with the `parallel` feature, as
+/// systems run by `process_parallel` borrow components from several threads.
+#[cfg(not(feature = "parallel"))]
+#[derive(Default)]
+pub struct ChangeList(RefCell<Vec<Entity>>);
+
+#[cfg(feature = "parallel")]
+#[derive(Default)]
+pub struct ChangeList(Mutex<Vec<Entity>>);
+
+impl ChangeList {
+    #[cfg(not(feature = "parallel"))]
+    pub fn push(&self, ent: Entity) {
+        self.0.borrow_mut().push(ent);
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn push(&self, ent: Entity) {
+        self.0.lock().unwrap().push(ent);
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    pub fn get_mut(&mut self) -> &mut Vec<Entity> {
+        self.0.get_mut()
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn get_mut(&mut self) -> &mut Vec<Entity> {
+        self.0.get_mut().unwrap()
+    }
+}
+
+
+/// Looks up the components of `entity` to reach their borrow flags. The flags
+/// are looked up again whenever they're needed rather than held on to, since
+/// adding components to an entity can move them.
+fn components(entity: &RefCell<Components>) -> &Components {
+    // The entity's components are only mutably borrowed by the world while
+    // adding a component, which never hands control to other code.
+    unsafe {
+        entity.try_borrow_unguarded().expect("entity borrowed while adding a component")
+    }
+}
+
+/// Finds `T` in `entity`, returning the entity's components along with the
+/// position of `T` and a pointer to it
+fn find<T: Any>(entity: &RefCell<Components>) -> Option<(&Components, usize, *mut T)> {
+    let components = unsafe { entity.try_borrow_unguarded().ok()? };
+    components.position(TypeId::of::<T>()).map(|i| (components, i, components.ptr(i) as *mut T))
+}
+
+/// A shared borrow of a component, as returned by `World::get_component_ref`.
+/// The component can't be borrowed mutably until every `ComponentRef` to it
+/// has been dropped.
+pub struct ComponentRef<'a, T: 'a> {
+    value: *const T,
+    entity: &'a RefCell<Components>,
+    index: usize,
+    marker: PhantomData<&'a T>
+}
+
+impl<'a, T: Any> ComponentRef<'a, T> {
+    /// Borrows `entity`'s component of type `T`
+    ///
+    /// # Panics
+    /// Panics if the component is currently mutably borrowed.
+    pub(crate) fn new(entity: &'a RefCell<Components>) -> Option<ComponentRef<'a, T>> {
+        find::<T>(entity).map(|(components, index, ptr)| {
+            if !components.flag(index).borrow() {
+                panic!("component already mutably borrowed");
+            }
+
+            ComponentRef {
+                value: ptr,
+                entity,
+                index,
+                marker: PhantomData
+            }
+        })
+    }
+}
+
+impl<'a, T> Deref for ComponentRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+impl<'a, T> Drop for ComponentRef<'a, T> {
+    fn drop(&mut self) {
+        components(self.entity).flag(self.index).release();
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for ComponentRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A mutable borrow of a component, as returned by `World::get_component`.
+/// The component can't be borrowed again until the `ComponentMut` is dropped.
+pub struct ComponentMut<'a, T: 'a> {
+    value: *mut T,
+    entity: &'a RefCell<Components>,
+    index: usize,
+    marker: PhantomData<&'a mut T>
+}
+
+impl<'a, T: Any> ComponentMut<'a, T> {
+    /// Mutably borrows `entity`'s component of type `T`, adding the entity, `ent`,
+    /// to `changed` if it isn't listed there already
+    ///
+    /// # Panics
+    /// Panics if the component is currently borrowed.
+    pub(crate) fn new(entity: &'a RefCell<Components>, ent: Entity, changed: &ChangeList) -> Option<ComponentMut<'a, T>> {
+        find::<T>(entity).map(|(components, index, ptr)| {
+            if !components.flag(index).borrow_mut() {
+                panic!("component already borrowed");
+            }
+            // There's no telling whether the component will actually be
+            // modified, so assume it is
+            components.mark_changed(index);
+            if components.mark_listed() {
+                changed.push(ent);
+            }
+
+            ComponentMut {
+                value: ptr,
+                entity,
+                index,
+                marker: PhantomData
+            }
+        })
+    }
+}
+
+impl<'a, T> Deref for ComponentMut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+impl<'a, T> DerefMut for ComponentMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value }
+    }
+}
+
+impl<'a, T> Drop for ComponentMut<'a, T> {
+    fn drop(&mut self) {
+        components(self.entity).flag(self.index).release_mut();
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for ComponentMut<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A shared borrow of a component made while its entity's components are
+/// already borrowed as a whole, such as while a query tests them. Those
+/// components can't move, so the flag is held on to directly.
+pub(crate) struct ScopedRef<'a, T: 'a> {
+    value: *const T,
+    flag: &'a BorrowFlag
+}
+
+impl<'a, T: Any> ScopedRef<'a, T> {
+    /// Borrows the component of type `T` in `components`
+    ///
+    /// # Panics
+    /// Panics if the component is currently mutably borrowed.
+    pub(crate) fn new(components: &'a Components) -> Option<ScopedRef<'a, T>> {
+        components.position(TypeId::of::<T>()).map(|index| {
+            let flag = components.flag(index);
+            if !flag.borrow() {
+                panic!("component already mutably borrowed");
+            }
+
+            ScopedRef {
+                value: components.ptr(index) as *const T,
+                flag
+            }
+        })
+    }
+}
+
+impl<'a, T> Deref for ScopedRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+impl<'a, T> Drop for ScopedRef<'a, T> {
+    fn drop(&mut self) {
+        self.flag.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_borrow_flag() {
+        let flag = BorrowFlag::new();
+        assert!(flag.borrow());
+        assert!(flag.borrow());
+        assert_eq!(flag.borrow_mut(), false);
+
+        flag.release();
+        flag.release();
+        assert_eq!(flag.is_borrowed(), false);
+
+        assert!(flag.borrow_mut());
+        assert_eq!(flag.borrow(), false);
+        assert_eq!(flag.borrow_mut(), false);
+
+        flag.release_mut();
+        assert!(flag.borrow());
+    }
+}
@@ -0,0 +1,34 @@
+use super::{Entity, World};
+
+/// A group of components that are added to an entity together, usually through
+/// `World::spawn_bundle`. Bundles can be derived for structs whose fields are
+/// all components, with fields marked `#[bundle(skip)]` left out.
+/// 
+/// # Examples
+/// ```
+/// extern crate apollo_ecs;
+/// use apollo_ecs::*;
+/// 
+/// struct Position(f32, f32);
+/// struct Health(u32);
+/// 
+/// #[derive(Bundle)]
+/// struct PlayerBundle {
+///     pos: Position,
+///     health: Health
+/// }
+/// 
+/// # fn main() {
+/// let mut world = World::new();
+/// let player = world.spawn_bundle(PlayerBundle {
+///     pos: Position(0.0, 0.0),
+///     health: Health(100)
+/// });
+/// 
+/// assert!(world.has_component::<Health>(player));
+/// # }
+/// ```
+pub trait Bundle {
+    /// Adds each of the bundle's components to entity `ent`
+    fn insert_into(self, world: &World, ent: Entity);
+}
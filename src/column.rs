@@ -0,0 +1,116 @@
+use super::prelude::any::Any;
+use super::prelude::mem::MaybeUninit;
+use super::prelude::ptr;
+use super::prelude::{Box, Vec};
+
+/// Number of values kept side by side in each block of a dense column
+const BLOCK_SIZE: usize = 1024;
+
+/// The storage shared by every component of a type registered as dense, as
+/// seen by code that doesn't know the type.
+pub(crate) trait Column {
+    /// Moves a boxed value into the column, returning where it now lives
+    fn insert_boxed(&mut self, value: Box<dyn Any>) -> *mut dyn Any;
+
+    /// Moves the value at `ptr` out of the column into a box, freeing its
+    /// place for reuse.
+    ///
+    /// # Safety
+    /// `ptr` must point at a value currently stored in this column.
+    unsafe fn remove_boxed(&mut self, ptr: *mut dyn Any) -> Box<dyn Any>;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Keeps values of `T` next to each other in fixed-size blocks, rather than in
+/// a box each. Blocks are never reallocated, so a value stays where it was put
+/// for as long as it's in the column and entities can keep pointing straight
+/// at it, even while other values are added and removed.
+pub(crate) struct DenseColumn<T> {
+    blocks: Vec<Box<[MaybeUninit<T>]>>,
+    /// Number of places handed out from the last block
+    used: usize,
+    /// Places given up by removed values, reused before handing out new ones
+    free: Vec<*mut T>
+}
+
+impl<T: Any> DenseColumn<T> {
+    pub fn new() -> DenseColumn<T> {
+        DenseColumn {
+            blocks: Vec::new(),
+            used: BLOCK_SIZE,
+            free: Vec::new()
+        }
+    }
+
+    /// Moves `value` into the column, returning where it now lives
+    pub fn insert(&mut self, value: T) -> *mut T {
+        let place = match self.free.pop() {
+            Some(place) => place,
+            None => {
+                if self.used == BLOCK_SIZE {
+                    self.blocks.push(Box::new_uninit_slice(BLOCK_SIZE));
+                    self.used = 0;
+                }
+
+                let place = self.blocks.last_mut().unwrap()[self.used].as_mut_ptr();
+                self.used += 1;
+                place
+            }
+        };
+
+        unsafe {
+            place.write(value);
+        }
+
+        place
+    }
+
+    /// Moves the value at `place` out of the column, freeing the place for
+    /// reuse.
+    ///
+    /// # Safety
+    /// `place` must point at a value currently stored in this column.
+    pub unsafe fn remove(&mut self, place: *mut T) -> T {
+        let value = ptr::read(place);
+        self.free.push(place);
+
+        value
+    }
+}
+
+impl<T: Any> Column for DenseColumn<T> {
+    fn insert_boxed(&mut self, value: Box<dyn Any>) -> *mut dyn Any {
+        let value = value.downcast::<T>().expect("value stored in the wrong column");
+        self.insert(*value)
+    }
+
+    unsafe fn remove_boxed(&mut self, ptr: *mut dyn Any) -> Box<dyn Any> {
+        Box::new(self.remove(ptr as *mut T))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dense_column() {
+        let mut column = DenseColumn::new();
+        let places: Vec<*mut usize> = (0..BLOCK_SIZE + 1).map(|i| column.insert(i)).collect();
+        assert_eq!(column.blocks.len(), 2);
+
+        // Values stay put as the column grows
+        for (i, &place) in places.iter().enumerate() {
+            assert_eq!(unsafe { *place }, i);
+        }
+
+        assert_eq!(unsafe { column.remove(places[3]) }, 3);
+        assert_eq!(column.insert(100), places[3]);
+        assert_eq!(unsafe { *places[3] }, 100);
+    }
+}
@@ -0,0 +1,42 @@
+use super::prelude::any::Any;
+use super::prelude::mem;
+
+/// How a component type's values are stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageKind {
+    /// Each value is boxed and owned by the entity holding it
+    Boxed,
+    /// The type holds no data, so only whether an entity has it matters
+    Tag
+}
+
+/// A type that can be added to entities as a component.
+/// 
+/// Every `'static` type is a component, described by its size: zero-sized
+/// types are tags, and anything else is boxed.
+pub trait Component: Any {
+    const STORAGE: StorageKind;
+}
+
+impl<T: Any> Component for T {
+    const STORAGE: StorageKind = if mem::size_of::<T>() == 0 {
+        StorageKind::Tag
+    } else {
+        StorageKind::Boxed
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_kind() {
+        struct Tag;
+        #[allow(dead_code)]
+        struct Health(u32);
+
+        assert_eq!(Tag::STORAGE, StorageKind::Tag);
+        assert_eq!(Health::STORAGE, StorageKind::Boxed);
+    }
+}
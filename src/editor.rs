@@ -0,0 +1,220 @@
+use super::{Entity, World};
+use super::borrow::{ComponentRef, ComponentMut};
+use super::component::Component;
+use super::prelude::fmt;
+
+/// A handle to a single live entity, as returned by `World::edit`, for adding
+/// and removing several of its components in a row
+pub struct EntityEditor<'a> {
+    world: &'a World,
+    ent: Entity
+}
+
+impl<'a> EntityEditor<'a> {
+    /// The ID of the entity being edited
+    pub fn id(&self) -> Entity {
+        self.ent
+    }
+
+    /// Adds `component` to the entity, replacing any component of the same
+    /// type, and returns the editor so further calls can be chained.
+    /// 
+    /// # Panics
+    /// Panics if the entity's existing `T` is currently borrowed.
+    pub fn add<T: Component>(&self, component: T) -> &Self {
+        self.world.add_component(self.ent, component);
+        self
+    }
+
+    /// Removes the entity's component of type `T` and returns it
+    pub fn remove<T: Component>(&self) -> Option<T> {
+        self.world.remove_component::<T>(self.ent)
+    }
+
+    /// Check whether the entity has a component of type `T`
+    pub fn has<T: Component>(&self) -> bool {
+        self.world.has_component::<T>(self.ent)
+    }
+
+    /// Mutably borrows the entity's component of type `T`, as `World::get_component`
+    pub fn get<T: Component>(&self) -> Option<ComponentMut<'a, T>> {
+        self.world.get_component::<T>(self.ent)
+    }
+
+    /// Mutably borrows the entity's component of type `T`, first adding
+    /// `T::default()` if it has none, as `World::get_component_or_default`
+    pub fn get_or_add<T: Component + Default>(&self) -> Option<ComponentMut<'a, T>> {
+        self.world.get_component_or_default::<T>(self.ent)
+    }
+
+    /// Borrows the entity's component of type `T`, as `World::get_component_ref`
+    pub fn get_ref<T: Component>(&self) -> Option<ComponentRef<'a, T>> {
+        self.world.get_component_ref::<T>(self.ent)
+    }
+}
+
+/// Lists the entity's components by type name with the `debug-names` feature,
+/// or by `TypeId` without it
+impl<'a> fmt::Debug for EntityEditor<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("EntityEditor");
+        debug.field("entity", &self.ent);
+        #[cfg(feature = "debug-names")]
+        debug.field("components", &self.world.type_name_of_components(self.ent));
+        #[cfg(not(feature = "debug-names"))]
+        debug.field("components", &self.world.components_of(self.ent));
+
+        debug.finish()
+    }
+}
+
+impl World {
+    /// Gets an editor for entity `ent`, or `None` if it isn't live.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// struct Position(f32, f32);
+    /// struct Velocity(f32, f32);
+    /// 
+    /// let mut world = World::new();
+    /// let ent = world.create_entity();
+    /// world.edit(ent).unwrap()
+    ///     .add(Position(0.0, 0.0))
+    ///     .add(Velocity(1.0, 0.0));
+    /// 
+    /// assert!(world.has_component::<Velocity>(ent));
+    /// ```
+    pub fn edit(&self, ent: Entity) -> Option<EntityEditor<'_>> {
+        if !self.is_valid(ent) {
+            return None;
+        }
+
+        Some(EntityEditor {
+            world: self,
+            ent
+        })
+    }
+
+    /// Creates an entity and returns an editor for it, so it can be built up in
+    /// one expression.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// struct Position(f32, f32);
+    /// struct Velocity(f32, f32);
+    /// 
+    /// let mut world = World::new();
+    /// let ent = world.spawn()
+    ///     .add(Position(0.0, 0.0))
+    ///     .add(Velocity(1.0, 0.0))
+    ///     .id();
+    /// 
+    /// assert!(world.has_component::<Position>(ent));
+    /// ```
+    pub fn spawn(&mut self) -> EntityEditor<'_> {
+        let ent = self.create_entity();
+
+        EntityEditor {
+            world: self,
+            ent
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit() {
+        #[derive(Debug, PartialEq)]
+        struct Name(&'static str);
+        struct Hidden;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        {
+            let editor = world.edit(ent).unwrap();
+            editor.add(Name("crate")).add(Hidden);
+            assert_eq!(editor.id(), ent);
+            assert!(editor.has::<Hidden>());
+
+            editor.get::<Name>().unwrap().0 = "barrel";
+            assert!(editor.remove::<Hidden>().is_some());
+            assert!(!editor.has::<Hidden>());
+        }
+
+        assert_eq!(*world.get_component_ref::<Name>(ent).unwrap(), Name("barrel"));
+
+        world.drop_entity(ent);
+        assert!(world.edit(ent).is_none());
+    }
+
+    #[test]
+    fn test_id() {
+        struct Marker;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        let editor = world.edit(ent).unwrap();
+        assert_eq!(editor.add(Marker).id(), ent);
+        assert!(editor.has::<Marker>());
+    }
+
+    #[test]
+    fn test_debug() {
+        use prelude::any;
+
+        struct Visible;
+        struct Hidden;
+
+        let mut world = World::new();
+        let editor = world.spawn();
+        editor.add(Visible).add(Hidden);
+
+        let debug = format!("{:?}", editor);
+        #[cfg(feature = "debug-names")]
+        let expected = [any::type_name::<Visible>(), any::type_name::<Hidden>()].map(String::from);
+        #[cfg(not(feature = "debug-names"))]
+        let expected = [any::TypeId::of::<Visible>(), any::TypeId::of::<Hidden>()].map(|ty| format!("{:?}", ty));
+        for component in expected.iter() {
+            assert!(debug.contains(component.as_str()), "{} doesn't mention {}", debug, component);
+        }
+    }
+
+    #[test]
+    fn test_get_or_add() {
+        #[derive(Default)]
+        struct Inventory(Vec<&'static str>);
+        struct Name(&'static str);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        {
+            let editor = world.edit(ent).unwrap();
+            editor.add(Name("crate")).get_or_add::<Inventory>().unwrap().0.push("apple");
+            editor.get_or_add::<Inventory>().unwrap().0.push("pear");
+        }
+
+        assert_eq!(world.get_component_ref::<Inventory>(ent).unwrap().0, vec!("apple", "pear"));
+        assert_eq!(world.get_component_ref::<Name>(ent).unwrap().0, "crate");
+    }
+
+    #[test]
+    fn test_spawn() {
+        struct Position(f32, f32);
+        struct Velocity(f32, f32);
+
+        let mut world = World::new();
+        let ent = world.spawn().add(Position(1.0, 2.0)).add(Velocity(3.0, 4.0)).id();
+
+        let pos = world.get_component::<Position>(ent).unwrap();
+        assert_eq!((pos.0, pos.1), (1.0, 2.0));
+        let vel = world.get_component::<Velocity>(ent).unwrap();
+        assert_eq!((vel.0, vel.1), (3.0, 4.0));
+    }
+}
@@ -0,0 +1,208 @@
+use super::Entity;
+use super::bitvec::BitVec;
+use super::prelude::fmt;
+use super::prelude::iter::FromIterator;
+
+/// A set of entities, such as a selection or the members of a group, stored
+/// as one bit per entity ID. Sets can be combined with `union`, `intersection`
+/// and `difference`, and the entities matching a query collected into one.
+/// 
+/// # Examples
+/// ```
+/// use apollo_ecs::*;
+/// 
+/// struct Enemy;
+/// struct Visible;
+/// 
+/// let mut world = World::new();
+/// let hidden = world.spawn().add(Enemy).id();
+/// let seen = world.spawn().add(Enemy).add(Visible).id();
+/// 
+/// let enemies: EntitySet = world.filter_entities(Matchers::with::<Enemy>()).collect();
+/// let visible: EntitySet = world.filter_entities(Matchers::with::<Visible>()).collect();
+/// assert_eq!(enemies.difference(&visible).iter().collect::<Vec<_>>(), vec!(hidden));
+/// assert!(enemies.intersection(&visible).contains(seen));
+/// ```
+#[derive(Clone)]
+pub struct EntitySet {
+    bits: BitVec
+}
+
+impl EntitySet {
+    /// Creates an empty set
+    pub fn new() -> EntitySet {
+        EntitySet::with_capacity(0)
+    }
+
+    /// Creates an empty set with room for entity IDs below `capacity` before it
+    /// has to grow
+    pub fn with_capacity(capacity: usize) -> EntitySet {
+        EntitySet {
+            bits: BitVec::new(capacity)
+        }
+    }
+
+    /// Adds `ent` to the set, returning whether it wasn't already in it
+    pub fn insert(&mut self, ent: Entity) -> bool {
+        if ent >= self.bits.len() {
+            // Grow by at least half again, so inserting IDs in ascending order
+            // doesn't reallocate every word
+            let bits = self.bits.len();
+            self.bits.grow((ent + 1).max(bits + bits / 2));
+        } else if self.bits.get(ent) {
+            return false;
+        }
+
+        self.bits.set(ent);
+        true
+    }
+
+    /// Removes `ent` from the set, returning whether it was in it
+    pub fn remove(&mut self, ent: Entity) -> bool {
+        if !self.contains(ent) {
+            return false;
+        }
+
+        self.bits.unset(ent);
+        true
+    }
+
+    /// Check whether `ent` is in the set
+    pub fn contains(&self, ent: Entity) -> bool {
+        ent < self.bits.len() && self.bits.get(ent)
+    }
+
+    /// Number of entities in the set
+    pub fn len(&self) -> usize {
+        self.bits.count_ones()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter_ones().next().is_none()
+    }
+
+    /// Removes every entity from the set, keeping its storage
+    pub fn clear(&mut self) {
+        self.bits = BitVec::new(self.bits.len());
+    }
+
+    /// Iterates over the entities in the set, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.bits.iter_ones()
+    }
+
+    /// The entities in either `self` or `other`
+    pub fn union(&self, other: &EntitySet) -> EntitySet {
+        let (a, b) = self.matched(other);
+        EntitySet { bits: a.or(&b) }
+    }
+
+    /// The entities in both `self` and `other`
+    pub fn intersection(&self, other: &EntitySet) -> EntitySet {
+        let (a, b) = self.matched(other);
+        EntitySet { bits: a.and(&b) }
+    }
+
+    /// The entities in `self` but not in `other`
+    pub fn difference(&self, other: &EntitySet) -> EntitySet {
+        let (a, b) = self.matched(other);
+        EntitySet { bits: a.and(&b.not()) }
+    }
+
+    /// Copies of the bits of both sets, grown to the same length
+    fn matched(&self, other: &EntitySet) -> (BitVec, BitVec) {
+        let len = self.bits.len().max(other.bits.len());
+        let (mut a, mut b) = (self.bits.clone(), other.bits.clone());
+        a.grow(len);
+        b.grow(len);
+
+        (a, b)
+    }
+}
+
+impl Default for EntitySet {
+    fn default() -> EntitySet {
+        EntitySet::new()
+    }
+}
+
+impl PartialEq for EntitySet {
+    fn eq(&self, other: &EntitySet) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for EntitySet {}
+
+impl FromIterator<Entity> for EntitySet {
+    fn from_iter<I: IntoIterator<Item = Entity>>(iter: I) -> EntitySet {
+        let mut set = EntitySet::new();
+        set.extend(iter);
+
+        set
+    }
+}
+
+impl Extend<Entity> for EntitySet {
+    fn extend<I: IntoIterator<Item = Entity>>(&mut self, iter: I) {
+        for ent in iter {
+            self.insert(ent);
+        }
+    }
+}
+
+impl fmt::Debug for EntitySet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ents: &[Entity]) -> EntitySet {
+        ents.iter().cloned().collect()
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut ents = EntitySet::new();
+        assert!(ents.is_empty());
+        assert!(!ents.contains(1000));
+
+        assert!(ents.insert(31));
+        assert!(ents.insert(32));
+        assert!(!ents.insert(32));
+        assert!(ents.insert(200));
+        assert_eq!(ents.len(), 3);
+        assert_eq!(ents.iter().collect::<Vec<_>>(), vec!(31, 32, 200));
+
+        assert!(ents.remove(32));
+        assert!(!ents.remove(32));
+        assert!(!ents.remove(5000));
+        assert!(ents.contains(31) && !ents.contains(32));
+
+        ents.clear();
+        assert!(ents.is_empty());
+        assert_eq!(format!("{:?}", set(&[1, 2])), "{1, 2}");
+    }
+
+    #[test]
+    fn test_set_operations() {
+        // Lengths differ, and members sit on either side of word boundaries
+        let a = set(&[0, 31, 32, 63]);
+        let b = set(&[31, 64, 95, 96, 130]);
+
+        assert_eq!(a.union(&b), set(&[0, 31, 32, 63, 64, 95, 96, 130]));
+        assert_eq!(b.union(&a), a.union(&b));
+        assert_eq!(a.intersection(&b), set(&[31]));
+        assert_eq!(b.intersection(&a), set(&[31]));
+        assert_eq!(a.difference(&b), set(&[0, 32, 63]));
+        assert_eq!(b.difference(&a), set(&[64, 95, 96, 130]));
+
+        assert!(a.intersection(&EntitySet::new()).is_empty());
+        assert_eq!(a.difference(&EntitySet::new()), a);
+        assert_eq!(EntitySet::with_capacity(1024), EntitySet::new());
+    }
+}
@@ -0,0 +1,140 @@
+//! A C interface for driving a world from another language.
+//! 
+//! Worlds are handed out as opaque pointers. Systems still have to be written
+//! in Rust and registered before the world is handed over, but entities can be
+//! created and removed, and given components, from the host. Components added
+//! through this interface are blobs of bytes identified by a type id of the
+//! caller's choosing, stored alongside the entity's Rust components. Blobs
+//! are copied in and out, and are only aligned to a byte, so they should be
+//! read with `memcpy` rather than cast to the host's type.
+//! 
+//! ```c
+//! ApolloWorld *world = apollo_world_new();
+//! size_t ent = apollo_world_create_entity(world);
+//! struct Position pos = { 0.0f, 0.0f };
+//! apollo_world_add_component_raw(world, ent, POSITION_ID, &pos, sizeof(pos));
+//! apollo_world_process(world);
+//! apollo_world_free(world);
+//! ```
+
+use super::{Entity, World};
+
+use std::collections::HashMap;
+use std::ptr;
+use std::slice;
+
+/// The blobs added to an entity through this interface, by type id
+#[derive(Default)]
+struct Blobs(HashMap<u64, Box<[u8]>>);
+
+/// Creates a new, empty world, to be freed with `apollo_world_free`
+#[no_mangle]
+pub extern "C" fn apollo_world_new() -> *mut World {
+    Box::into_raw(Box::new(World::new()))
+}
+
+/// Frees a world created by `apollo_world_new`, or handed over with
+/// `Box::into_raw`.
+/// 
+/// # Safety
+/// `world` must be null or a world from `apollo_world_new` that hasn't
+/// already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn apollo_world_free(world: *mut World) {
+    if !world.is_null() {
+        drop(Box::from_raw(world));
+    }
+}
+
+/// Creates an entity and returns its ID
+/// 
+/// # Safety
+/// `world` must be a live world from `apollo_world_new`.
+#[no_mangle]
+pub unsafe extern "C" fn apollo_world_create_entity(world: *mut World) -> Entity {
+    (*world).create_entity()
+}
+
+/// Schedules entity `ent` to be removed at the end of the current tick, like
+/// `World::remove_entity`
+/// 
+/// # Safety
+/// `world` must be a live world from `apollo_world_new`.
+#[no_mangle]
+pub unsafe extern "C" fn apollo_world_remove_entity(world: *mut World, ent: Entity) {
+    (*world).remove_entity(ent);
+}
+
+/// Copies `size` bytes from `data` into a component of type `type_id` on
+/// entity `ent`, replacing any component of that type the entity already
+/// has. Returns false if the entity is invalid.
+/// 
+/// # Safety
+/// `world` must be a live world from `apollo_world_new`, and `data` must be
+/// valid for reads of `size` bytes. `data` may be null if `size` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn apollo_world_add_component_raw(world: *mut World, ent: Entity, type_id: u64, data: *const u8, size: usize) -> bool {
+    let bytes = if size == 0 {
+        Box::default()
+    } else {
+        slice::from_raw_parts(data, size).into()
+    };
+
+    match (*world).get_or_insert_component(ent, Blobs::default) {
+        Some(mut blobs) => {
+            blobs.0.insert(type_id, bytes);
+            true
+        },
+        None => false
+    }
+}
+
+/// Finds entity `ent`'s component of type `type_id`, returning a pointer to
+/// its bytes and writing their length to `size`, or returning null if the
+/// entity is invalid or has no such component.
+/// 
+/// The bytes can be modified in place, and stay valid until the component is
+/// replaced or removed, or the entity is dropped. They aren't borrow tracked.
+/// 
+/// # Safety
+/// `world` must be a live world from `apollo_world_new`, and `size` must be
+/// null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn apollo_world_get_component_raw(world: *mut World, ent: Entity, type_id: u64, size: *mut usize) -> *mut u8 {
+    let mut blobs = match (*world).get_component::<Blobs>(ent) {
+        Some(blobs) => blobs,
+        None => return ptr::null_mut()
+    };
+
+    match blobs.0.get_mut(&type_id) {
+        Some(bytes) => {
+            if !size.is_null() {
+                *size = bytes.len();
+            }
+            bytes.as_mut_ptr()
+        },
+        None => ptr::null_mut()
+    }
+}
+
+/// Removes entity `ent`'s component of type `type_id`, returning whether it
+/// had one
+/// 
+/// # Safety
+/// `world` must be a live world from `apollo_world_new`.
+#[no_mangle]
+pub unsafe extern "C" fn apollo_world_remove_component_raw(world: *mut World, ent: Entity, type_id: u64) -> bool {
+    match (*world).get_component::<Blobs>(ent) {
+        Some(mut blobs) => blobs.0.remove(&type_id).is_some(),
+        None => false
+    }
+}
+
+/// Runs a tick, like `World::process`
+/// 
+/// # Safety
+/// `world` must be a live world from `apollo_world_new`.
+#[no_mangle]
+pub unsafe extern "C" fn apollo_world_process(world: *mut World) {
+    (*world).process();
+}
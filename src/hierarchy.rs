@@ -0,0 +1,214 @@
+use super::{Entity, World};
+use super::prelude::{HashMap, Vec};
+
+/// The entity an entity is parented to, maintained by `World::set_parent`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parent(pub Entity);
+
+/// The entities parented to an entity, in the order they were parented,
+/// maintained by `World::set_parent`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Children(pub Vec<Entity>);
+
+impl World {
+    /// Parents `child` to `parent`, moving it out of its previous parent's
+    /// `Children` if it had one. Returns `false`, leaving the hierarchy as it
+    /// was, if either entity is invalid or `child` is `parent` or one of its
+    /// ancestors.
+    /// 
+    /// An entity removed from the world is taken out of its parent's
+    /// `Children`, and its own children lose their `Parent`. Use
+    /// `despawn_recursive` to remove an entity along with its descendants.
+    pub fn set_parent(&self, child: Entity, parent: Entity) -> bool {
+        if !self.is_valid(child) || !self.is_valid(parent) {
+            return false;
+        }
+
+        let mut ancestor = Some(parent);
+        while let Some(ent) = ancestor {
+            if ent == child {
+                return false;
+            }
+
+            ancestor = self.get_component_ref::<Parent>(ent).map(|p| p.0);
+        }
+
+        self.detach(child);
+        self.add_component(child, Parent(parent));
+
+        let has_children = match self.get_component::<Children>(parent) {
+            Some(mut children) => {
+                children.0.push(child);
+                true
+            },
+            None => false
+        };
+        if !has_children {
+            self.add_component(parent, Children(vec!(child)));
+        }
+
+        true
+    }
+
+    /// Immediately removes `ent` and all of its descendants from the world, and
+    /// removes `ent` from its parent's `Children`.
+    pub fn despawn_recursive(&mut self, ent: Entity) {
+        if !self.is_valid(ent) {
+            return;
+        }
+
+        self.detach(ent);
+
+        let mut stack = vec!(ent);
+        while let Some(ent) = stack.pop() {
+            if let Some(children) = self.get_component_ref::<Children>(ent) {
+                stack.extend(children.0.iter().cloned());
+            }
+
+            self.drop_entity(ent);
+        }
+    }
+
+    /// Points the `Parent` and `Children` components of the moved entities at
+    /// the new IDs of the entities they name, as listed in `moved`, which maps
+    /// old IDs to new ones. Links to entities that weren't moved are dropped.
+    pub(crate) fn remap_hierarchy(&self, moved: &HashMap<Entity, Entity>) {
+        for &ent in moved.values() {
+            let parent = self.get_component_ref::<Parent>(ent).map(|parent| parent.0);
+            if let Some(parent) = parent {
+                match moved.get(&parent) {
+                    Some(&new) => self.get_component::<Parent>(ent).unwrap().0 = new,
+                    None => {
+                        self.remove_component::<Parent>(ent);
+                    }
+                }
+            }
+            if let Some(mut children) = self.get_component::<Children>(ent) {
+                children.0 = children.0.iter().filter_map(|child| moved.get(child).cloned()).collect();
+            }
+        }
+    }
+
+    /// Takes `ent` out of the hierarchy as it's freed: removes it from its
+    /// parent's `Children`, and removes the `Parent` of each of its children, so
+    /// that neither refers to its ID once it's reused
+    pub(crate) fn unlink(&self, ent: Entity) {
+        self.detach(ent);
+
+        let children = match self.get_component_ref::<Children>(ent) {
+            Some(children) => children.0.clone(),
+            None => return
+        };
+        for child in children {
+            let orphaned = self.get_component_ref::<Parent>(child).is_some_and(|parent| parent.0 == ent);
+            if orphaned {
+                self.remove_component::<Parent>(child);
+            }
+        }
+    }
+
+    /// Removes `child` from its parent's `Children`, if it has a parent
+    fn detach(&self, child: Entity) {
+        let parent = match self.get_component_ref::<Parent>(child) {
+            Some(parent) => parent.0,
+            None => return
+        };
+
+        if let Some(mut children) = self.get_component::<Children>(parent) {
+            children.0.retain(|&ent| ent != child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_despawn_recursive() {
+        struct Name(&'static str);
+
+        let mut world = World::new();
+        let mut spawn = |name| {
+            let ent = world.create_entity();
+            world.add_component(ent, Name(name));
+            ent
+        };
+
+        let root = spawn("root");
+        let arm = spawn("arm");
+        let hand = spawn("hand");
+        let finger = spawn("finger");
+        let leg = spawn("leg");
+        let other = spawn("other");
+        let other_child = spawn("other child");
+
+        assert!(world.set_parent(arm, root));
+        assert!(world.set_parent(leg, root));
+        assert!(world.set_parent(hand, arm));
+        assert!(world.set_parent(finger, hand));
+        assert!(world.set_parent(other_child, other));
+
+        assert_eq!(*world.get_component_ref::<Children>(root).unwrap(), Children(vec!(arm, leg)));
+        assert_eq!(*world.get_component_ref::<Parent>(finger).unwrap(), Parent(hand));
+
+        world.despawn_recursive(root);
+
+        for &ent in [root, arm, hand, finger, leg].iter() {
+            assert!(!world.has_component::<Name>(ent));
+        }
+        assert_eq!(world.get_component_ref::<Name>(other).unwrap().0, "other");
+        assert_eq!(world.get_component_ref::<Name>(other_child).unwrap().0, "other child");
+    }
+
+    #[test]
+    fn test_set_parent() {
+        let mut world = World::new();
+        let a = world.create_entity();
+        let b = world.create_entity();
+        let c = world.create_entity();
+
+        assert!(world.set_parent(b, a));
+        assert!(world.set_parent(c, b));
+
+        // Cycles are rejected
+        assert!(!world.set_parent(a, c));
+        assert!(!world.set_parent(a, a));
+        assert!(world.get_component_ref::<Parent>(a).is_none());
+
+        // Reparenting moves the child between parents
+        assert!(world.set_parent(c, a));
+        assert_eq!(*world.get_component_ref::<Children>(a).unwrap(), Children(vec!(b, c)));
+        assert_eq!(*world.get_component_ref::<Children>(b).unwrap(), Children(vec!()));
+
+        // Despawning a child removes it from its parent
+        world.despawn_recursive(b);
+        assert_eq!(*world.get_component_ref::<Children>(a).unwrap(), Children(vec!(c)));
+    }
+
+    #[test]
+    fn test_drop_entity_unlinks() {
+        struct Name(&'static str);
+
+        let mut world = World::new();
+        let parent = world.create_entity();
+        let child = world.create_entity();
+        let grandchild = world.create_entity();
+        assert!(world.set_parent(child, parent));
+        assert!(world.set_parent(grandchild, child));
+
+        world.drop_entity(child);
+        assert_eq!(*world.get_component_ref::<Children>(parent).unwrap(), Children(vec!()));
+        assert!(world.get_component_ref::<Parent>(grandchild).is_none());
+
+        // The dropped ID is reused by an unrelated entity, which the parent's
+        // despawn mustn't take with it
+        let reused = world.create_entity();
+        assert_eq!(reused, child);
+        world.add_component(reused, Name("reused"));
+
+        world.despawn_recursive(parent);
+        assert_eq!(world.get_component_ref::<Name>(reused).unwrap().0, "reused");
+        assert!(world.is_valid(grandchild));
+    }
+}
@@ -1,4 +1,6 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(feature = "nightly", feature(test))]
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
 
 //! Apollo is a lightwieght entity-component-system framework written in pure
 //! Rust. 
@@ -47,10 +49,45 @@
 //! ```
 //! 
 //! To read more about ECS check [here](http://entity-systems.wikidot.com/)
+//! 
+//! # `no_std`
+//! 
+//! The crate only needs `core` and `alloc` when built without its default
+//! `std` feature. The `parallel`, `sync` and `tracing` features require `std`.
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+extern crate apollo_ecs_derive;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
+mod prelude;
 mod world;
 mod query;
+mod archetype;
+mod borrow;
+mod column;
+mod bundle;
+mod component;
+mod editor;
+mod entity_set;
+mod hierarchy;
+mod plugin;
+mod registry;
+mod snapshot;
+mod spatial;
+#[cfg(feature = "parallel")]
+mod schedule;
+#[cfg(feature = "sync")]
+mod sync;
+#[allow(dead_code)]
 mod bitvec;
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
 /// Contains traits for implementing various modes of entity processing
 /// in systems
 pub mod systems;
@@ -58,5 +95,18 @@ pub mod systems;
 /// An entity's ID
 pub type Entity = usize;
 
-pub use world::World;
-pub use query::{Matchers, Query as EntityQuery};
\ No newline at end of file
+pub use world::{World, WorldBuilder, GrowthPolicy, ReusePolicy, FrameContext, SystemHandle, TickStats, WorldError, ComponentError, DEFAULT_STAGE};
+pub use borrow::{ComponentRef, ComponentMut};
+pub use bundle::Bundle;
+pub use component::{Component, StorageKind};
+pub use editor::EntityEditor;
+pub use entity_set::EntitySet;
+pub use hierarchy::{Parent, Children};
+pub use plugin::Plugin;
+pub use registry::UnknownComponent;
+pub use snapshot::{EntitySnapshot, CloneError};
+pub use spatial::SpatialGrid;
+#[cfg(feature = "sync")]
+pub use sync::SyncWorld;
+pub use apollo_ecs_derive::Bundle;
+pub use query::{Matchers, QueryBuilder, Query as EntityQuery, MatchedEntities, MatchedIter};
\ No newline at end of file
@@ -0,0 +1,85 @@
+use super::World;
+
+/// A reusable bundle of systems and resources, such as everything a physics
+/// or input layer needs, added to a world in one go with `World::add_plugin`.
+/// 
+/// # Examples
+/// ```
+/// use apollo_ecs::*;
+/// 
+/// struct Gravity(f32);
+/// 
+/// struct PhysicsPlugin {
+///     gravity: f32
+/// }
+/// 
+/// impl Plugin for PhysicsPlugin {
+///     fn build(&self, world: &mut World) {
+///         world.insert_resource(Gravity(self.gravity));
+///     }
+/// }
+/// 
+/// let mut world = World::new();
+/// world.add_plugin(PhysicsPlugin { gravity: 9.8 });
+/// assert_eq!(world.get_resource::<Gravity>().unwrap().0, 9.8);
+/// ```
+pub trait Plugin {
+    /// Registers the plugin's systems and inserts its resources into `world`
+    fn build(&self, world: &mut World);
+}
+
+impl World {
+    /// Adds everything `plugin` provides to the world, returning the world so
+    /// further plugins can be chained
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut World {
+        plugin.build(self);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Entity;
+
+    #[test]
+    fn test_add_plugin() {
+        use systems::IterativeSystem;
+        use query::{Query, Matchers};
+
+        struct Speed(u32);
+        struct Position(u32);
+
+        struct Move;
+
+        impl IterativeSystem for Move {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Position>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                let speed = world.get_resource::<Speed>().unwrap().0;
+                world.get_component::<Position>(ent).unwrap().0 += speed;
+            }
+        }
+
+        struct MovementPlugin;
+
+        impl Plugin for MovementPlugin {
+            fn build(&self, world: &mut World) {
+                world.insert_resource(Speed(3));
+                world.register_iterative_system(Move);
+            }
+        }
+
+        let mut world = World::new();
+        world.add_plugin(MovementPlugin);
+        assert!(world.is_system_registered::<Move>());
+
+        let ent = world.create_entity();
+        world.add_component(ent, Position(0));
+        world.process();
+        world.process();
+        assert_eq!(world.get_component_ref::<Position>(ent).unwrap().0, 6);
+    }
+}
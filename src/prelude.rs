@@ -0,0 +1,33 @@
+//! Everything the crate uses from the standard library, taken from `core` and
+//! `alloc` instead when built without the `std` feature. Without `std` maps
+//! are ordered rather than hashed.
+
+#[cfg(feature = "std")]
+pub use std::{any, cell, cmp, fmt, iter, marker, mem, ops, ptr, slice};
+#[cfg(feature = "std")]
+pub use std::boxed::Box;
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "std")]
+pub use std::sync::Arc;
+#[cfg(feature = "std")]
+pub use std::sync::atomic;
+#[cfg(feature = "std")]
+pub use std::string::String;
+#[cfg(feature = "std")]
+pub use std::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+pub use core::{any, cell, cmp, fmt, iter, marker, mem, ops, ptr, slice};
+#[cfg(not(feature = "std"))]
+pub use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+pub use alloc::collections::{BTreeMap as HashMap, VecDeque};
+#[cfg(not(feature = "std"))]
+pub use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+pub use core::sync::atomic;
+#[cfg(not(feature = "std"))]
+pub use alloc::string::String;
+#[cfg(not(feature = "std"))]
+pub use alloc::vec::Vec;
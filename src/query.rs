@@ -1,9 +1,39 @@
-use super::world::Component;
-use std::any::{Any, TypeId};
-use std::cell::RefCell;
+use super::Entity;
+use super::world::Components;
+use super::borrow::ScopedRef;
+use super::prelude::any::{self, Any, TypeId};
+use super::prelude::cell::RefCell;
+use super::prelude::cmp::Ordering;
+use super::prelude::marker::PhantomData;
+use super::prelude::slice;
+use super::prelude::{Arc, Box, String, Vec};
 
 pub trait Condition {
-    fn test(&self, components: &RefCell<Vec<Component>>) -> bool; 
+    /// Tests an entity's components, which the caller borrows once for the
+    /// whole condition tree
+    fn test(&self, components: &Components) -> bool;
+
+    /// Tests an archetype signature, a sorted list of the component
+    /// types shared by every entity in that archetype. Returns `None`
+    /// if the result depends on the values of the entities' components,
+    /// in which case each entity must be tested individually.
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool>;
+
+    /// Appends the component types this condition marks as optional
+    fn optional_types(&self, _types: &mut Vec<TypeId>) {}
+
+    /// Appends a human-readable rendering of this condition
+    fn describe(&self, out: &mut String);
+
+    /// Whether this condition's description combines several conditions, and
+    /// so needs parentheses when nested in another
+    fn is_compound(&self) -> bool {
+        false
+    }
+
+    /// Copies this condition into a new box, so that a builder can be built
+    /// more than once
+    fn clone_box(&self) -> Box<dyn Condition>;
 }
 
 /// Contains static methods for building entity queries.
@@ -25,36 +55,196 @@ impl Matchers {
         QueryBuilder::new().without::<T>()
     }
 
+    /// Always matches, but records `T` as a component the query's users will
+    /// make use of when an entity has it. The optional types of a built query
+    /// are available from [`EntityQuery::optional_types`](struct.EntityQuery.html#method.optional_types).
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// use apollo_ecs::systems::IterativeSystem;
+    /// 
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    /// 
+    /// struct MoveSystem;
+    /// 
+    /// impl IterativeSystem for MoveSystem {
+    ///     fn get_query() -> EntityQuery {
+    ///         EntityQuery::new(Matchers::with::<Position>().optional::<Velocity>())
+    ///     }
+    /// 
+    ///     fn process(&mut self, ent: Entity, world: &World) {
+    ///         let mut pos = world.get_component::<Position>(ent).unwrap();
+    ///         if let Some(vel) = world.get_component_ref::<Velocity>(ent) {
+    ///             pos.0 += vel.0;
+    ///         }
+    ///     }
+    /// }
+    /// 
+    /// let mut world = World::new();
+    /// world.register_iterative_system(MoveSystem);
+    /// 
+    /// let still = world.create_entity();
+    /// world.add_component(still, Position(0.0));
+    /// let moving = world.create_entity();
+    /// world.add_component(moving, Position(0.0));
+    /// world.add_component(moving, Velocity(2.0));
+    /// 
+    /// world.process();
+    /// assert_eq!(world.get_component::<Position>(still).unwrap().0, 0.0);
+    /// assert_eq!(world.get_component::<Position>(moving).unwrap().0, 2.0);
+    /// ```
+    pub fn optional<T>() -> QueryBuilder where T: Any {
+        QueryBuilder::new().optional::<T>()
+    }
+
+    /// Tests whether an entity has a component of every type in `with` and none
+    /// of the types in `without`, for queries whose types are only known at
+    /// runtime, such as ones loaded from data. Equivalent to chaining `with`
+    /// and `without` for each type, but `describe` can't name the types, and
+    /// shows `?` in their place.
+    pub fn from_spec(with: &[TypeId], without: &[TypeId]) -> QueryBuilder {
+        QueryBuilder::new().from_spec(with, without)
+    }
+
+    /// Tests whether an entity has a component of type `T` for which `predicate`
+    /// returns true.
+    /// 
+    /// The predicate is handed a shared reference to the component each time a
+    /// system using the query is about to run, so its result always reflects
+    /// the component's current value. Testing the query borrows the component,
+    /// so it panics if a mutable borrow of the same component, as returned by
+    /// `World::get_component`, is still alive.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// struct Health(u32);
+    /// 
+    /// let query = EntityQuery::new(Matchers::where_component(|health: &Health| health.0 < 10));
+    /// ```
+    pub fn where_component<T, F>(predicate: F) -> QueryBuilder where T: Any, F: Fn(&T) -> bool + 'static {
+        QueryBuilder::new().where_component(predicate)
+    }
+
+    /// Tests whether an entity has a component of type `T` equal to `expected`,
+    /// such as a state tag. Borrows the component while testing it, like
+    /// [`where_component`](#method.where_component).
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// #[derive(PartialEq)]
+    /// enum State {
+    ///     Running,
+    ///     Paused
+    /// }
+    /// 
+    /// let query = EntityQuery::new(Matchers::with_value(State::Paused));
+    /// ```
+    pub fn with_value<T>(expected: T) -> QueryBuilder where T: Any + PartialEq {
+        QueryBuilder::new().with_value(expected)
+    }
+
+    /// Tests whether an entity has a component of type `T` that was added, or
+    /// mutably borrowed through `World::get_component`, during the current tick.
+    /// Changes are forgotten at the end of each tick, so systems only see changes
+    /// made by the systems that ran before them in the same tick.
+    /// 
+    /// The world can't tell whether a mutable borrow was actually used to modify
+    /// the component, so every call to `get_component` counts as a change. Read
+    /// components with `get_component_ref` to avoid marking them.
+    pub fn changed<T>() -> QueryBuilder where T: Any {
+        QueryBuilder::new().changed::<T>()
+    }
+
+    /// Tests whether a component of type `T` was added to an entity during the
+    /// previous tick, or between it and the current one, and is still there.
+    /// Each addition is matched during exactly one tick.
+    pub fn added<T>() -> QueryBuilder where T: Any {
+        QueryBuilder::new().added::<T>()
+    }
+
+    /// Tests whether a component of type `T` was removed from an entity with
+    /// `World::remove_component` during the previous tick, or between it and
+    /// the current one, whatever components the entity has now. Each removal
+    /// is matched during exactly one tick.
+    pub fn removed<T>() -> QueryBuilder where T: Any {
+        QueryBuilder::new().removed::<T>()
+    }
+
+    /// Tests whether the number of components an entity has compares to `n` as
+    /// `ordering`, so that `component_count(Ordering::Greater, 2)` matches
+    /// entities with more than two components. Every component counts,
+    /// including those the world maintains itself such as `Parent` and
+    /// `Children`.
+    pub fn component_count(ordering: Ordering, n: usize) -> QueryBuilder {
+        QueryBuilder::new().component_count(ordering, n)
+    }
+
+    /// Tests `condition` as a single unit, whatever it's built from, so that
+    /// `(A or B) and (C or D)` can be written as
+    /// 
+    /// ```
+    /// # use apollo_ecs::*;
+    /// # struct A; struct B; struct C; struct D;
+    /// let query = Matchers::group(Matchers::with::<A>().or(Matchers::with::<B>()))
+    ///     .group(Matchers::with::<C>().or(Matchers::with::<D>()))
+    ///     .build();
+    /// ```
+    pub fn group<T>(condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        QueryBuilder::new().group(condition)
+    }
+
     /// True if the left-hand side of this expression, and `condition` 
     /// both test as true.
-    pub fn and<T>(condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
+    pub fn and<T>(condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
         QueryBuilder::new().and(condition)
     }
 
     /// True if the left-hand side of this expression, and `condition` 
     /// both test as false.
-    pub fn and_not<T>(condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
+    pub fn and_not<T>(condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
         QueryBuilder::new().and_not(condition)
     }
 
     /// True if either the left-hand side of this expression, or `condition` 
     /// test as true.
-    pub fn or<T>(condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
+    pub fn or<T>(condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
         QueryBuilder::new().or(condition)
     }
 
     /// True if either the left-hand side of this expression, or `condition` 
     /// test as false.
-    pub fn or_not<T>(condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
+    pub fn or_not<T>(condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
         QueryBuilder::new().or_not(condition)
     }
+
+    /// True if `condition` as a whole tests as false, so that
+    /// `Matchers::not(Matchers::with::<A>().with::<B>())` matches entities
+    /// missing either `A` or `B`.
+    pub fn not<T>(condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        QueryBuilder::new().not(condition)
+    }
 }
 
+/// Builds up an [`EntityQuery`](struct.EntityQuery.html) from conditions, read
+/// from left to right. Conditions added by methods such as `with` and
+/// `without` must all hold. `and`, `or` and their negated forms instead combine
+/// everything built so far, as a single group, with their argument, and any
+/// conditions added after them must hold alongside that combination:
+/// `with::<A>().or(Matchers::with::<B>()).with::<C>()` reads as
+/// `(A or B) and C`, never `A or (B and C)`. Use
+/// [`Matchers::group`](struct.Matchers.html#method.group) to spell out the
+/// grouping wherever it might surprise.
 pub struct QueryBuilder {
-    conditions: Vec<Box<Condition>>,
+    conditions: Vec<Box<dyn Condition>>,
 }
 
-impl <'a> QueryBuilder {
+impl QueryBuilder {
     pub fn new() -> QueryBuilder {
         QueryBuilder {
             conditions: Vec::new()
@@ -70,7 +260,8 @@ impl <'a> QueryBuilder {
     /// Identical to [`Matchers.with`](struct.Matchers.html#method.with)
     pub fn with<T>(mut self) -> QueryBuilder where T: Any {
         self.conditions.push(Box::new(IsCondition { 
-            ty: TypeId::of::<T>() 
+            ty: TypeId::of::<T>(),
+            name: any::type_name::<T>()
         }));
         
         self
@@ -79,51 +270,154 @@ impl <'a> QueryBuilder {
     /// Identical to [`Matchers.without`](struct.Matchers.html#method.without)
     pub fn without<T>(mut self) -> QueryBuilder where T: Any {
         self.conditions.push(Box::new(IsNotCondition {
-            ty: TypeId::of::<T>()
+            ty: TypeId::of::<T>(),
+            name: any::type_name::<T>()
         }));
 
         self
     }
 
-    /// Identical to [`Matchers.and`](struct.Matchers.html#method.and)
-    pub fn and<T>(self, condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
-        let mut new_builder = QueryBuilder::new();
-        new_builder.conditions.push(Box::new(AndCondition {
-            left: Box::new(self.build()),
-            right: condition.into()
+    /// Identical to [`Matchers.from_spec`](struct.Matchers.html#method.from_spec)
+    pub fn from_spec(mut self, with: &[TypeId], without: &[TypeId]) -> QueryBuilder {
+        for &ty in with.iter() {
+            self.conditions.push(Box::new(IsCondition { ty, name: "?" }));
+        }
+        for &ty in without.iter() {
+            self.conditions.push(Box::new(IsNotCondition { ty, name: "?" }));
+        }
+
+        self
+    }
+
+    /// Identical to [`Matchers.optional`](struct.Matchers.html#method.optional)
+    pub fn optional<T>(mut self) -> QueryBuilder where T: Any {
+        self.conditions.push(Box::new(OptionalCondition {
+            ty: TypeId::of::<T>(),
+            name: any::type_name::<T>()
         }));
 
-        new_builder
+        self
     }
 
-    /// Identical to [`Matchers.and_not`](struct.Matchers.html#method.and_not)
-    pub fn and_not<T>(self, condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
-        let mut new_builder = QueryBuilder::new();
-        new_builder.conditions.push(Box::new(AndCondition {
-            left: Box::new(self.build()),
-            right: Box::new(NotCondition { cond: condition.into() })
+    /// Identical to [`Matchers.where_component`](struct.Matchers.html#method.where_component)
+    pub fn where_component<T, F>(mut self, predicate: F) -> QueryBuilder where T: Any, F: Fn(&T) -> bool + 'static {
+        self.conditions.push(Box::new(WhereCondition {
+            ty: TypeId::of::<T>(),
+            name: any::type_name::<T>(),
+            predicate: Arc::new(predicate),
+            component: PhantomData
         }));
 
-        new_builder
+        self
+    }
+
+    /// Identical to [`Matchers.with_value`](struct.Matchers.html#method.with_value)
+    pub fn with_value<T>(mut self, expected: T) -> QueryBuilder where T: Any + PartialEq {
+        self.conditions.push(Box::new(ValueCondition {
+            name: any::type_name::<T>(),
+            expected: Arc::new(expected)
+        }));
+
+        self
+    }
+
+    /// Identical to [`Matchers.changed`](struct.Matchers.html#method.changed)
+    pub fn changed<T>(mut self) -> QueryBuilder where T: Any {
+        self.conditions.push(Box::new(ChangedCondition {
+            ty: TypeId::of::<T>(),
+            name: any::type_name::<T>()
+        }));
+
+        self
+    }
+
+    /// Identical to [`Matchers.added`](struct.Matchers.html#method.added)
+    pub fn added<T>(mut self) -> QueryBuilder where T: Any {
+        self.conditions.push(Box::new(AddedCondition {
+            ty: TypeId::of::<T>(),
+            name: any::type_name::<T>()
+        }));
+
+        self
+    }
+
+    /// Identical to [`Matchers.removed`](struct.Matchers.html#method.removed)
+    pub fn removed<T>(mut self) -> QueryBuilder where T: Any {
+        self.conditions.push(Box::new(RemovedCondition {
+            ty: TypeId::of::<T>(),
+            name: any::type_name::<T>()
+        }));
+
+        self
+    }
+
+    /// Identical to [`Matchers.component_count`](struct.Matchers.html#method.component_count)
+    pub fn component_count(mut self, ordering: Ordering, n: usize) -> QueryBuilder {
+        self.conditions.push(Box::new(ComponentCountCondition {
+            ordering,
+            n
+        }));
+
+        self
+    }
+
+    /// Identical to [`Matchers.group`](struct.Matchers.html#method.group)
+    pub fn group<T>(mut self, condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        self.conditions.push(condition.into());
+
+        self
+    }
+
+    /// Identical to [`Matchers.and`](struct.Matchers.html#method.and)
+    pub fn and<T>(self, condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        self.join(condition.into(), |left, right| Box::new(AndCondition { left, right }))
+    }
+
+    /// Identical to [`Matchers.and_not`](struct.Matchers.html#method.and_not)
+    pub fn and_not<T>(self, condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        self.join(Box::new(NotCondition { cond: condition.into() }), |left, right| Box::new(AndCondition { left, right }))
     }
 
     /// Identical to [`Matchers.or`](struct.Matchers.html#method.or)
-    pub fn or<T>(self, condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
+    pub fn or<T>(self, condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        self.join(condition.into(), |left, right| Box::new(OrCondition { left, right }))
+    }
+
+    /// Identical to [`Matchers.or_not`](struct.Matchers.html#method.or_not)
+    pub fn or_not<T>(self, condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        self.join(Box::new(NotCondition { cond: condition.into() }), |left, right| Box::new(OrCondition { left, right }))
+    }
+
+    /// Joins the conditions so far to `right` with `join`. With no conditions
+    /// so far, which would match nothing, `right` stands on its own instead.
+    #[allow(clippy::type_complexity)]
+    fn join(mut self, right: Box<dyn Condition>, join: fn(Box<dyn Condition>, Box<dyn Condition>) -> Box<dyn Condition>) -> QueryBuilder {
+        if self.conditions.is_empty() {
+            self.conditions.push(right);
+            return self;
+        }
+
         let mut new_builder = QueryBuilder::new();
-        new_builder.conditions.push(Box::new(OrCondition {
-            left: Box::new(self.build()),
-            right: condition.into()
-        }));
+        new_builder.conditions.push(join(Box::new(self.build()), right));
 
         new_builder
     }
 
-    /// Identical to [`Matchers.or_not`](struct.Matchers.html#method.or_not)
-    pub fn or_not<T>(self, condition: T) -> QueryBuilder where T: Into<Box<Condition>> {
+    /// Identical to [`Matchers.not`](struct.Matchers.html#method.not)
+    pub fn not<T>(mut self, condition: T) -> QueryBuilder where T: Into<Box<dyn Condition>> {
+        self.conditions.push(Box::new(NotCondition {
+            cond: condition.into()
+        }));
+
+        self
+    }
+
+    /// Negates everything built so far, so that the resulting query matches
+    /// exactly the entities this one doesn't.
+    pub fn negate(self) -> QueryBuilder {
         let mut new_builder = QueryBuilder::new();
-        new_builder.conditions.push(Box::new(OrCondition {
-            left: Box::new(self.build()),
-            right: Box::new(NotCondition { cond: condition.into() })
+        new_builder.conditions.push(Box::new(NotCondition {
+            cond: Box::new(self.build())
         }));
 
         new_builder
@@ -135,28 +429,84 @@ impl <'a> QueryBuilder {
             conditions: self.conditions
         }
     }
+
+    /// Returns a finalized [`EntityQuery`](struct.EntityQuery.html) from a copy
+    /// of this builder's conditions, leaving the builder to be built again or
+    /// extended. Predicates given to `where_component` are shared between the
+    /// copies rather than cloned.
+    pub fn build_ref(&self) -> Query {
+        Query {
+            conditions: self.conditions.iter().map(|condition| condition.clone_box()).collect()
+        }
+    }
 }
 
-impl Into<Box<Condition>> for QueryBuilder {
-    fn into(self) -> Box<Condition> {
-        Box::new(self.build())
+impl Default for QueryBuilder {
+    fn default() -> QueryBuilder {
+        QueryBuilder::new()
+    }
+}
+
+impl From<QueryBuilder> for Box<dyn Condition> {
+    fn from(builder: QueryBuilder) -> Box<dyn Condition> {
+        Box::new(builder.build())
     }
 }
 
 /// Represents a set of rules for filtering entities before
-/// they are passed into a system as part of a world tick
+/// they are passed into a system as part of a world tick.
+/// 
+/// An entity matches a query when it passes every condition the query was
+/// built from. A query built from no conditions at all, such as
+/// `QueryBuilder::new().build()`, matches nothing; use
+/// [`Matchers::any`](struct.Matchers.html#method.any) to match every entity.
 pub struct Query {
-    conditions: Vec<Box<Condition>>
+    conditions: Vec<Box<dyn Condition>>
+}
+
+impl Clone for Query {
+    fn clone(&self) -> Query {
+        Query {
+            conditions: self.conditions.iter().map(|condition| condition.clone_box()).collect()
+        }
+    }
 }
 
 impl Query {
     pub fn new(builder: QueryBuilder) -> Query {
         builder.build()
     }
+
+    /// The component types marked as optional anywhere in this query
+    pub fn optional_types(&self) -> Vec<TypeId> {
+        let mut types = Vec::new();
+        Condition::optional_types(self, &mut types);
+
+        types
+    }
+
+    /// Tests `entity`'s components, borrowing them once for every condition
+    pub(crate) fn matches(&self, entity: &RefCell<Components>) -> bool {
+        self.test(&entity.borrow())
+    }
+
+    /// Renders this query's conditions in a human-readable form, such as
+    /// `with<A> AND (without<B> OR with<C>)`, for working out why an entity
+    /// does or doesn't match.
+    pub fn describe(&self) -> String {
+        let mut out = String::new();
+        Condition::describe(self, &mut out);
+
+        out
+    }
 }
 
 impl Condition for Query {
-    fn test(&self, components: &RefCell<Vec<Component>>) -> bool {
+    fn test(&self, components: &Components) -> bool {
+        if self.conditions.is_empty() {
+            return false;
+        }
+
         for condition in self.conditions.iter() {
             if !condition.test(components) {
                 return false;
@@ -165,83 +515,543 @@ impl Condition for Query {
 
         true
     }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        if self.conditions.is_empty() {
+            return Some(false);
+        }
+
+        let mut result = Some(true);
+        for condition in self.conditions.iter() {
+            match condition.test_signature(signature) {
+                Some(false) => return Some(false),
+                Some(true) => (),
+                None => result = None
+            }
+        }
+
+        result
+    }
+
+    fn optional_types(&self, types: &mut Vec<TypeId>) {
+        for condition in self.conditions.iter() {
+            condition.optional_types(types);
+        }
+    }
+
+    fn describe(&self, out: &mut String) {
+        match self.conditions.len() {
+            0 => return out.push_str("nothing"),
+            1 => return self.conditions[0].describe(out),
+            _ => ()
+        }
+
+        for (i, condition) in self.conditions.iter().enumerate() {
+            if i > 0 {
+                out.push_str(" AND ");
+            }
+            describe_operand(&**condition, out);
+        }
+    }
+
+    fn is_compound(&self) -> bool {
+        match self.conditions.len() {
+            0 => false,
+            1 => self.conditions[0].is_compound(),
+            _ => true
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
+    }
+}
+
+/// Describes `condition` as part of a larger one, in parentheses if needed
+fn describe_operand(condition: &dyn Condition, out: &mut String) {
+    if condition.is_compound() {
+        out.push('(');
+        condition.describe(out);
+        out.push(')');
+    } else {
+        condition.describe(out);
+    }
+}
+
+/// Describes a condition on a single component type, such as `with<A>`
+fn describe_typed(label: &str, name: &str, out: &mut String) {
+    out.push_str(label);
+    out.push('<');
+    out.push_str(name);
+    out.push('>');
+}
+
+/// A snapshot of the entities matching a query, collected by
+/// [`World::collect_matching`](struct.World.html#method.collect_matching)
+pub struct MatchedEntities {
+    entities: Vec<Entity>
+}
+
+impl MatchedEntities {
+    pub(crate) fn new(entities: Vec<Entity>) -> MatchedEntities {
+        MatchedEntities {
+            entities
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Iterates over the set's entities in ascending order
+    pub fn iter(&self) -> MatchedIter<'_> {
+        MatchedIter {
+            inner: self.entities.iter()
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a MatchedEntities {
+    type Item = Entity;
+    type IntoIter = MatchedIter<'a>;
+
+    fn into_iter(self) -> MatchedIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over a [`MatchedEntities`](struct.MatchedEntities.html) set, which
+/// always knows how many entities are left
+pub struct MatchedIter<'a> {
+    inner: slice::Iter<'a, Entity>
+}
+
+impl<'a> Iterator for MatchedIter<'a> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        self.inner.next().cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for MatchedIter<'a> {}
+
+impl<'a> DoubleEndedIterator for MatchedIter<'a> {
+    fn next_back(&mut self) -> Option<Entity> {
+        self.inner.next_back().cloned()
+    }
 }
 
+#[derive(Clone)]
 struct AnyCondition;
 
+#[derive(Clone)]
 struct IsCondition {
-    ty: TypeId
+    ty: TypeId,
+    name: &'static str
 }
 
+#[derive(Clone)]
 struct IsNotCondition {
-    ty: TypeId
+    ty: TypeId,
+    name: &'static str
 }
 
 struct AndCondition {
-    left: Box<Condition>,
-    right: Box<Condition>
+    left: Box<dyn Condition>,
+    right: Box<dyn Condition>
 }
 
 struct OrCondition {
-    left: Box<Condition>,
-    right: Box<Condition>
+    left: Box<dyn Condition>,
+    right: Box<dyn Condition>
 }
 
 struct NotCondition {
-    cond: Box<Condition>
+    cond: Box<dyn Condition>
+}
+
+#[derive(Clone)]
+struct OptionalCondition {
+    ty: TypeId,
+    name: &'static str
+}
+
+#[derive(Clone)]
+struct ChangedCondition {
+    ty: TypeId,
+    name: &'static str
+}
+
+#[derive(Clone)]
+struct AddedCondition {
+    ty: TypeId,
+    name: &'static str
+}
+
+#[derive(Clone)]
+struct RemovedCondition {
+    ty: TypeId,
+    name: &'static str
+}
+
+#[derive(Clone)]
+struct ComponentCountCondition {
+    ordering: Ordering,
+    n: usize
+}
+
+struct ValueCondition<T> {
+    name: &'static str,
+    /// Shared by the copies `build_ref` makes, which may end up on other
+    /// threads with the `sync` feature
+    expected: Arc<T>
+}
+
+struct WhereCondition<T, F> {
+    ty: TypeId,
+    name: &'static str,
+    predicate: Arc<F>,
+    component: PhantomData<fn(&T)>
 }
 
 impl Condition for AnyCondition {
-    fn test(&self, _components: &RefCell<Vec<Component>>) -> bool {
+    fn test(&self, _components: &Components) -> bool {
         true
     }
+
+    fn test_signature(&self, _signature: &[TypeId]) -> Option<bool> {
+        Some(true)
+    }
+
+    fn describe(&self, out: &mut String) {
+        out.push_str("any");
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
+    }
 }
 
 impl Condition for IsCondition {
-    fn test(&self, components: &RefCell<Vec<Component>>) -> bool {
-        for &(ty, _) in components.borrow().iter() {
-            if ty == self.ty {
-                return true;
-            }
-        }
+    fn test(&self, components: &Components) -> bool {
+        components.contains(self.ty)
+    }
 
-        false
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        Some(signature.binary_search(&self.ty).is_ok())
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("with", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
     }
 }
 
 impl Condition for IsNotCondition {
-    fn test(&self, components: &RefCell<Vec<Component>>) -> bool {
-        for &(ty, _) in components.borrow().iter() {
-            if ty == self.ty {
-                return false;
-            }
-        }
+    fn test(&self, components: &Components) -> bool {
+        !components.contains(self.ty)
+    }
 
-        true
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        Some(signature.binary_search(&self.ty).is_err())
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("without", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
     }
 }
 
 impl Condition for AndCondition {
-    fn test(&self, components: &RefCell<Vec<Component>>) -> bool {
+    fn test(&self, components: &Components) -> bool {
         self.left.test(components) && self.right.test(components)
     }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        match (self.left.test_signature(signature), self.right.test_signature(signature)) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None
+        }
+    }
+
+    fn optional_types(&self, types: &mut Vec<TypeId>) {
+        self.left.optional_types(types);
+        self.right.optional_types(types);
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_operand(&*self.left, out);
+        out.push_str(" AND ");
+        describe_operand(&*self.right, out);
+    }
+
+    fn is_compound(&self) -> bool {
+        true
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(AndCondition {
+            left: self.left.clone_box(),
+            right: self.right.clone_box()
+        })
+    }
 }
 
 impl Condition for OrCondition {
-    fn test(&self, components: &RefCell<Vec<Component>>) -> bool {
+    fn test(&self, components: &Components) -> bool {
         self.left.test(components) || self.right.test(components)
     }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        match (self.left.test_signature(signature), self.right.test_signature(signature)) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None
+        }
+    }
+
+    fn optional_types(&self, types: &mut Vec<TypeId>) {
+        self.left.optional_types(types);
+        self.right.optional_types(types);
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_operand(&*self.left, out);
+        out.push_str(" OR ");
+        describe_operand(&*self.right, out);
+    }
+
+    fn is_compound(&self) -> bool {
+        true
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(OrCondition {
+            left: self.left.clone_box(),
+            right: self.right.clone_box()
+        })
+    }
 }
 
 impl Condition for NotCondition {
-    fn test(&self, components: &RefCell<Vec<Component>>) -> bool {
+    fn test(&self, components: &Components) -> bool {
         !self.cond.test(components)
     }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        self.cond.test_signature(signature).map(|result| !result)
+    }
+
+    fn optional_types(&self, types: &mut Vec<TypeId>) {
+        self.cond.optional_types(types);
+    }
+
+    fn describe(&self, out: &mut String) {
+        out.push_str("NOT ");
+        describe_operand(&*self.cond, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(NotCondition {
+            cond: self.cond.clone_box()
+        })
+    }
+}
+
+impl Condition for OptionalCondition {
+    fn test(&self, _components: &Components) -> bool {
+        true
+    }
+
+    fn test_signature(&self, _signature: &[TypeId]) -> Option<bool> {
+        Some(true)
+    }
+
+    fn optional_types(&self, types: &mut Vec<TypeId>) {
+        types.push(self.ty);
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("optional", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
+    }
+}
+
+impl<T, F> Condition for WhereCondition<T, F> where T: Any, F: Fn(&T) -> bool + 'static {
+    fn test(&self, components: &Components) -> bool {
+        match ScopedRef::<T>::new(components) {
+            Some(component) => (self.predicate)(&component),
+            None => false
+        }
+    }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        match signature.binary_search(&self.ty) {
+            Ok(_) => None,
+            Err(_) => Some(false)
+        }
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("where", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(WhereCondition {
+            ty: self.ty,
+            name: self.name,
+            predicate: self.predicate.clone(),
+            component: PhantomData
+        })
+    }
+}
+
+impl<T> Condition for ValueCondition<T> where T: Any + PartialEq {
+    fn test(&self, components: &Components) -> bool {
+        match ScopedRef::<T>::new(components) {
+            Some(component) => *component == *self.expected,
+            None => false
+        }
+    }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        match signature.binary_search(&TypeId::of::<T>()) {
+            Ok(_) => None,
+            Err(_) => Some(false)
+        }
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("with_value", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(ValueCondition {
+            name: self.name,
+            expected: self.expected.clone()
+        })
+    }
+}
+
+impl Condition for ChangedCondition {
+    fn test(&self, components: &Components) -> bool {
+        components.changed(self.ty)
+    }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        match signature.binary_search(&self.ty) {
+            Ok(_) => None,
+            Err(_) => Some(false)
+        }
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("changed", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
+    }
+}
+
+impl Condition for AddedCondition {
+    fn test(&self, components: &Components) -> bool {
+        components.contains(self.ty) && components.added(self.ty)
+    }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        match signature.binary_search(&self.ty) {
+            Ok(_) => None,
+            Err(_) => Some(false)
+        }
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("added", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
+    }
+}
+
+impl Condition for RemovedCondition {
+    fn test(&self, components: &Components) -> bool {
+        components.removed(self.ty)
+    }
+
+    fn test_signature(&self, _signature: &[TypeId]) -> Option<bool> {
+        None
+    }
+
+    fn describe(&self, out: &mut String) {
+        describe_typed("removed", self.name, out);
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
+    }
+}
+
+impl Condition for ComponentCountCondition {
+    fn test(&self, components: &Components) -> bool {
+        components.len().cmp(&self.n) == self.ordering
+    }
+
+    fn test_signature(&self, signature: &[TypeId]) -> Option<bool> {
+        // An entity holds at most one component of each type, so its
+        // archetype's signature lists exactly as many types as it has components
+        Some(signature.len().cmp(&self.n) == self.ordering)
+    }
+
+    fn describe(&self, out: &mut String) {
+        let op = match self.ordering {
+            Ordering::Less => "<",
+            Ordering::Equal => "=",
+            Ordering::Greater => ">"
+        };
+        out.push_str(&format!("component_count {} {}", op, self.n));
+    }
+
+    fn clone_box(&self) -> Box<dyn Condition> {
+        Box::new(self.clone())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use world::Component;
+
+    fn components(list: Vec<Component>) -> Components {
+        list.into()
+    }
+
+    /// The components of an entity holding each of `types` whose bit is set
+    /// in `mask`, all pointing at `value`
+    fn ents_with(types: &[TypeId], mask: usize, value: &mut i32) -> Components {
+        let ptr = value as *mut i32 as *mut dyn Any;
+        components(types.iter().enumerate().filter(|&(i, _)| mask & (1 << i) != 0).map(|(_, &ty)| (ty, ptr)).collect())
+    }
 
     #[test]
     fn test_query_builder() {
@@ -250,8 +1060,8 @@ mod tests {
         
         let query = Matchers::with::<A>().with::<B>().build();
 
-        assert!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any), (TypeId::of::<B>(), &mut 2 as *mut Any)))));
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any)))), false);
+        assert!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any), (TypeId::of::<B>(), &mut 2 as *mut dyn Any)))));
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), false);
     }
 
     #[test]
@@ -261,9 +1071,9 @@ mod tests {
 
         let query = Matchers::any().build();
 
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any), (TypeId::of::<B>(), &mut 2 as *mut Any)))), true);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any)))), true);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<B>(), &mut 1 as *mut Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any), (TypeId::of::<B>(), &mut 2 as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<B>(), &mut 1 as *mut dyn Any)))), true);
     }
 
     #[test]
@@ -274,9 +1084,9 @@ mod tests {
         
         let query = Matchers::without::<A>().and_not(Matchers::with::<B>()).build();
 
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any), (TypeId::of::<B>(), &mut 2 as *mut Any)))), false);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any)))), false);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<C>(), &mut 1 as *mut Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any), (TypeId::of::<B>(), &mut 2 as *mut dyn Any)))), false);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), false);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<C>(), &mut 1 as *mut dyn Any)))), true);
     }
 
     #[test]
@@ -287,9 +1097,9 @@ mod tests {
         
         let query = Matchers::with::<A>().without::<B>().build();
 
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any), (TypeId::of::<B>(), &mut 2 as *mut Any)))), false);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any)))), true);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<C>(), &mut 1 as *mut Any)))), false);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any), (TypeId::of::<B>(), &mut 2 as *mut dyn Any)))), false);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<C>(), &mut 1 as *mut dyn Any)))), false);
     }
 
     #[test]
@@ -299,14 +1109,353 @@ mod tests {
         struct C;
         
         let query = Matchers::with::<A>().or(Matchers::with::<B>()).build();
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any)))), true);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<B>(), &mut 1 as *mut Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<B>(), &mut 1 as *mut dyn Any)))), true);
+
+        let query = Matchers::with::<A>().without::<B>().or(Matchers::with::<C>()).build();
+
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any), (TypeId::of::<B>(), &mut 2 as *mut dyn Any)))), false);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<C>(), &mut 1 as *mut dyn Any)))), true);
+    }
+
+    #[test]
+    fn test_not_compound() {
+        struct A;
+        struct B;
+        struct C;
+
+        let (a, b, c) = (TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>());
+        let types = [a, b, c];
+        let mut value = 0;
+
+        // not (A and B) == (not A) or (not B)
+        let query = Matchers::not(Matchers::with::<A>().with::<B>()).build();
+        let expected = Matchers::without::<A>().or(Matchers::without::<B>()).build();
+        for mask in 0..8 {
+            let ents = ents_with(&types, mask, &mut value);
+            assert_eq!(query.test(&ents), expected.test(&ents));
+        }
+        assert_eq!(query.test(&ents_with(&types, 0b011, &mut value)), false);
+
+        // not (A or B) == (not A) and (not B)
+        let query = Matchers::with::<A>().or(Matchers::with::<B>()).negate().build();
+        let expected = Matchers::without::<A>().without::<B>().build();
+        for mask in 0..8 {
+            let ents = ents_with(&types, mask, &mut value);
+            assert_eq!(query.test(&ents), expected.test(&ents));
+        }
+
+        // Composes with further conditions
+        let query = Matchers::with::<C>().not(Matchers::with::<A>().or(Matchers::with::<B>())).build();
+        assert_eq!(query.test(&ents_with(&types, 0b100, &mut value)), true);
+        assert_eq!(query.test(&ents_with(&types, 0b101, &mut value)), false);
+        assert_eq!(query.test(&ents_with(&types, 0, &mut value)), false);
+
+        let mut signature = vec!(b, c);
+        signature.sort();
+        assert_eq!(query.test_signature(&[c]), Some(true));
+        assert_eq!(query.test_signature(&signature), Some(false));
+        assert_eq!(Matchers::with::<A>().negate().negate().build().test_signature(&[a]), Some(true));
+    }
+
+    #[test]
+    fn test_signature() {
+        struct A;
+        struct B;
+        struct C;
 
         let query = Matchers::with::<A>().without::<B>().or(Matchers::with::<C>()).build();
 
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any), (TypeId::of::<B>(), &mut 2 as *mut Any)))), false);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut 1 as *mut Any)))), true);
-        assert_eq!(query.test(&RefCell::new(vec!((TypeId::of::<C>(), &mut 1 as *mut Any)))), true);
+        let mut signature = vec!(TypeId::of::<A>(), TypeId::of::<B>());
+        signature.sort();
+        assert_eq!(query.test_signature(&signature), Some(false));
+        assert_eq!(query.test_signature(&[TypeId::of::<A>()]), Some(true));
+        assert_eq!(query.test_signature(&[TypeId::of::<C>()]), Some(true));
+        assert_eq!(query.test_signature(&[]), Some(false));
+    }
+
+    #[test]
+    fn test_optional() {
+        struct A;
+        struct B;
+        struct C;
+
+        let query = Matchers::with::<A>().optional::<B>().or(Matchers::with::<C>().optional::<A>()).build();
+
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any), (TypeId::of::<B>(), &mut 2 as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<B>(), &mut 1 as *mut dyn Any)))), false);
+        assert_eq!(query.test_signature(&[TypeId::of::<A>()]), Some(true));
+        assert_eq!(query.optional_types(), vec!(TypeId::of::<B>(), TypeId::of::<A>()));
+        assert_eq!(Matchers::with::<A>().build().optional_types(), vec!());
+    }
+
+    #[test]
+    fn test_where_component() {
+        struct Health(u32);
+        struct B;
+
+        let query = Matchers::where_component(|health: &Health| health.0 < 10).build();
+
+        assert_eq!(query.test(&components(vec!((TypeId::of::<Health>(), &mut Health(5) as *mut dyn Any)))), true);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<Health>(), &mut Health(50) as *mut dyn Any)))), false);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<B>(), &mut B as *mut dyn Any)))), false);
+
+        assert_eq!(query.test_signature(&[TypeId::of::<Health>()]), None);
+        assert_eq!(query.test_signature(&[TypeId::of::<B>()]), Some(false));
+
+        let query = Matchers::with::<B>().or(Matchers::where_component(|health: &Health| health.0 < 10)).build();
+        assert_eq!(query.test_signature(&[TypeId::of::<B>()]), Some(true));
+        assert_eq!(query.test_signature(&[TypeId::of::<Health>()]), None);
+        assert_eq!(query.test_signature(&[]), Some(false));
+
+        let query = Matchers::without::<B>().and_not(Matchers::where_component(|health: &Health| health.0 < 10)).build();
+        assert_eq!(query.test_signature(&[TypeId::of::<B>()]), Some(false));
+        assert_eq!(query.test_signature(&[TypeId::of::<Health>()]), None);
+        assert_eq!(query.test_signature(&[]), Some(true));
+    }
+
+    #[test]
+    fn test_component_count() {
+        struct A;
+        struct B;
+
+        let mut value = 0;
+        let (a, b) = (TypeId::of::<A>(), TypeId::of::<B>());
+        let types = [a, b];
+
+        let query = Matchers::component_count(Ordering::Greater, 1).build();
+        assert_eq!(query.test(&ents_with(&types, 0b11, &mut value)), true);
+        assert_eq!(query.test(&ents_with(&types, 0b01, &mut value)), false);
+        assert_eq!(query.test_signature(&[a]), Some(false));
+
+        let query = Matchers::component_count(Ordering::Equal, 1).build();
+        assert_eq!(query.test(&ents_with(&types, 0b10, &mut value)), true);
+        assert_eq!(query.test(&ents_with(&types, 0, &mut value)), false);
+        assert_eq!(query.test(&ents_with(&types, 0b11, &mut value)), false);
+
+        let query = Matchers::with::<A>().component_count(Ordering::Less, 2).build();
+        assert_eq!(query.test(&ents_with(&types, 0b01, &mut value)), true);
+        assert_eq!(query.test(&ents_with(&types, 0b11, &mut value)), false);
+        assert_eq!(query.test_signature(&[a]), Some(true));
+        assert_eq!(query.describe(), format!("with<{}> AND component_count < 2", any::type_name::<A>()));
+    }
+
+    #[test]
+    fn test_group() {
+        struct A;
+        struct B;
+        struct C;
+        struct D;
+
+        let types = [TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>(), TypeId::of::<D>()];
+        let mut value = 0;
+
+        // (A or B) and (C or D)
+        let query = Matchers::group(Matchers::with::<A>().or(Matchers::with::<B>()))
+            .group(Matchers::with::<C>().or(Matchers::with::<D>()))
+            .build();
+        let same = Matchers::with::<A>().or(Matchers::with::<B>())
+            .and(Matchers::with::<C>().or(Matchers::with::<D>()))
+            .build();
+        // (A and B) or (C and D)
+        let other = Matchers::group(Matchers::with::<A>().with::<B>())
+            .or(Matchers::with::<C>().with::<D>())
+            .build();
+
+        for mask in 0..16 {
+            let has = |i: usize| mask & (1 << i) != 0;
+            let ents = ents_with(&types, mask, &mut value);
+            assert_eq!(query.test(&ents), (has(0) || has(1)) && (has(2) || has(3)));
+            assert_eq!(same.test(&ents), query.test(&ents));
+            assert_eq!(other.test(&ents), (has(0) && has(1)) || (has(2) && has(3)));
+        }
+
+        let (a, b, c, d) = (any::type_name::<A>(), any::type_name::<B>(), any::type_name::<C>(), any::type_name::<D>());
+        assert_eq!(query.describe(), format!("(with<{}> OR with<{}>) AND (with<{}> OR with<{}>)", a, b, c, d));
+    }
+
+    #[test]
+    fn test_conditions_after_or() {
+        struct A;
+        struct B;
+        struct C;
+
+        let types = [TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>()];
+        let mut value = 0;
+
+        // (A or B) and C
+        let query = Matchers::with::<A>().or(Matchers::with::<B>()).with::<C>().build();
+        // (A and B) or C
+        let and_first = Matchers::with::<A>().with::<B>().or(Matchers::with::<C>()).build();
+        // ((A or B) and not C) or A
+        let chained = Matchers::with::<A>().or(Matchers::with::<B>()).without::<C>().or(Matchers::with::<A>()).build();
+
+        for mask in 0..8 {
+            let has = |i: usize| mask & (1 << i) != 0;
+            let ents = ents_with(&types, mask, &mut value);
+            assert_eq!(query.test(&ents), (has(0) || has(1)) && has(2));
+            assert_eq!(and_first.test(&ents), (has(0) && has(1)) || has(2));
+            assert_eq!(chained.test(&ents), ((has(0) || has(1)) && !has(2)) || has(0));
+        }
+
+        let (a, b, c) = (any::type_name::<A>(), any::type_name::<B>(), any::type_name::<C>());
+        assert_eq!(query.describe(), format!("(with<{}> OR with<{}>) AND with<{}>", a, b, c));
+    }
+
+    #[test]
+    fn test_empty_query() {
+        struct A;
+
+        let query = QueryBuilder::new().build();
+        assert_eq!(query.test(&components(vec!())), false);
+        assert_eq!(query.test(&components(vec!((TypeId::of::<A>(), &mut 1 as *mut dyn Any)))), false);
+        assert_eq!(query.test_signature(&[]), Some(false));
+
+        // Only the empty query itself matches nothing
+        let query = Matchers::not(QueryBuilder::new()).build();
+        assert_eq!(query.test(&components(vec!())), true);
+    }
+
+    #[test]
+    fn test_static_combinators() {
+        struct A;
+        struct B;
+
+        let types = [TypeId::of::<A>(), TypeId::of::<B>()];
+        let mut value = 0;
+
+        // With nothing on their left, these stand for their condition alone
+        let and = Matchers::and(Matchers::with::<A>()).build();
+        let and_not = Matchers::and_not(Matchers::with::<A>()).build();
+        let or = Matchers::or(Matchers::with::<A>()).build();
+        let or_not = Matchers::or_not(Matchers::with::<A>()).build();
+        for mask in 0..4 {
+            let ents = ents_with(&types, mask, &mut value);
+            let has_a = mask & 1 != 0;
+            assert_eq!(and.test(&ents), has_a);
+            assert_eq!(and_not.test(&ents), !has_a);
+            assert_eq!(or.test(&ents), has_a);
+            assert_eq!(or_not.test(&ents), !has_a);
+        }
+
+        let query = Matchers::and(Matchers::with::<A>()).or(Matchers::with::<B>()).build();
+        assert_eq!(query.test(&ents_with(&types, 0b10, &mut value)), true);
+        assert_eq!(query.test(&ents_with(&types, 0, &mut value)), false);
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        struct A;
+        struct B;
+
+        let mut builder = Matchers::with::<A>();
+        for i in 0..64 {
+            builder = if i % 2 == 0 {
+                builder.and(Matchers::any())
+            } else {
+                builder.or(Matchers::with::<B>().without::<A>())
+            };
+        }
+        let query = builder.build();
+
+        let mut value = 0;
+        assert!(query.test(&components(vec!((TypeId::of::<A>(), &mut value as *mut dyn Any)))));
+        assert!(query.test(&components(vec!((TypeId::of::<B>(), &mut value as *mut dyn Any)))));
+        assert_eq!(query.test(&components(vec!())), false);
+        assert_eq!(query.test_signature(&[TypeId::of::<B>()]), Some(true));
+    }
+
+    #[test]
+    fn test_describe() {
+        use std::any::type_name;
+
+        struct A;
+        struct B;
+        struct C;
+
+        let (a, b, c) = (type_name::<A>(), type_name::<B>(), type_name::<C>());
+
+        let query = Matchers::with::<A>().and(Matchers::without::<B>().or(Matchers::with::<C>())).build();
+        assert_eq!(query.describe(), format!("with<{}> AND (without<{}> OR with<{}>)", a, b, c));
+
+        let query = Matchers::with::<A>().changed::<B>().or(Matchers::not(Matchers::with::<C>().without::<A>())).build();
+        assert_eq!(query.describe(), format!("(with<{}> AND changed<{}>) OR NOT (with<{}> AND without<{}>)", a, b, c, a));
+
+        assert_eq!(Matchers::any().build().describe(), "any");
+        assert_eq!(QueryBuilder::new().build().describe(), "nothing");
+    }
+
+    #[test]
+    fn test_with_value() {
+        #[derive(PartialEq)]
+        enum State {
+            Running,
+            Paused
+        }
+
+        let (mut running, mut paused) = (State::Running, State::Paused);
+        let query = Matchers::with_value(State::Paused).build();
+        let ty = TypeId::of::<State>();
+        assert!(query.test(&components(vec!((ty, &mut paused as *mut dyn Any)))));
+        assert!(!query.test(&components(vec!((ty, &mut running as *mut dyn Any)))));
+        assert!(!query.test(&components(vec!())));
+
+        assert_eq!(query.test_signature(&[]), Some(false));
+        assert_eq!(query.test_signature(&[ty]), None);
+        assert_eq!(query.describe(), format!("with_value<{}>", any::type_name::<State>()));
+    }
+
+    #[test]
+    fn test_from_spec() {
+        struct A;
+        struct B;
+        struct C;
+
+        let types = [TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>()];
+        let mut value = 0;
+
+        let generic = Matchers::with::<A>().with::<B>().without::<C>().build();
+        let spec = Matchers::from_spec(&types[..2], &types[2..]).build();
+        for mask in 0..8 {
+            let ents = ents_with(&types, mask, &mut value);
+            assert_eq!(spec.test(&ents), generic.test(&ents));
+        }
+
+        let mut signature = types.to_vec();
+        signature.sort();
+        assert_eq!(spec.test_signature(&signature), Some(false));
+        assert_eq!(spec.test_signature(&signature[..2]), generic.test_signature(&signature[..2]));
+        assert_eq!(spec.describe(), "with<?> AND with<?> AND without<?>");
+    }
+
+    #[test]
+    fn test_build_ref() {
+        struct A;
+        struct B;
+
+        let builder = Matchers::with::<A>().or(Matchers::with::<B>()).where_component(|value: &i32| *value > 0);
+        let first = builder.build_ref();
+        let second = builder.build_ref();
+
+        let (mut positive, mut zero) = (1, 0);
+        let ents = [
+            components(vec!((TypeId::of::<A>(), &mut positive as *mut dyn Any), (TypeId::of::<i32>(), &mut positive as *mut dyn Any))),
+            components(vec!((TypeId::of::<B>(), &mut zero as *mut dyn Any), (TypeId::of::<i32>(), &mut zero as *mut dyn Any))),
+            components(vec!((TypeId::of::<i32>(), &mut positive as *mut dyn Any)))
+        ];
+        for ent in ents.iter() {
+            assert_eq!(first.test(ent), second.test(ent));
+        }
+        assert!(first.test(&ents[0]));
+        assert!(!first.test(&ents[1]));
+        assert!(!first.test(&ents[2]));
+        assert_eq!(first.describe(), second.describe());
+
+        // The builder can still be extended and built after being copied
+        let query = builder.without::<A>().build();
+        assert!(!query.test(&ents[0]));
     }
 }
 
@@ -328,7 +1477,7 @@ mod benches {
         let query = Matchers::with::<A>().without::<B>().or(Matchers::with::<C>()).build();
         
         b.iter(|| {
-            query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut test::black_box(1) as *mut Any), (TypeId::of::<B>(), &mut test::black_box(2) as *mut Any))));
+            query.test(&Components::from(vec!((TypeId::of::<A>(), &mut test::black_box(1) as *mut dyn Any), (TypeId::of::<B>(), &mut test::black_box(2) as *mut dyn Any))));
         });
     }
 
@@ -340,7 +1489,7 @@ mod benches {
         let query = Matchers::with::<A>().build();
 
         b.iter(|| {
-            query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut test::black_box(1) as *mut Any), (TypeId::of::<B>(), &mut test::black_box(2) as *mut Any))));
+            query.test(&Components::from(vec!((TypeId::of::<A>(), &mut test::black_box(1) as *mut dyn Any), (TypeId::of::<B>(), &mut test::black_box(2) as *mut dyn Any))));
         });
     }
 
@@ -354,7 +1503,7 @@ mod benches {
         let query = Matchers::with::<A>().with::<B>().with::<C>().without::<D>().build();
 
         b.iter(|| {
-            query.test(&RefCell::new(vec!((TypeId::of::<A>(), &mut test::black_box(1) as *mut Any), (TypeId::of::<B>(), &mut test::black_box(2) as *mut Any), (TypeId::of::<C>(), &mut test::black_box(3) as *mut Any))));
+            query.test(&Components::from(vec!((TypeId::of::<A>(), &mut test::black_box(1) as *mut dyn Any), (TypeId::of::<B>(), &mut test::black_box(2) as *mut dyn Any), (TypeId::of::<C>(), &mut test::black_box(3) as *mut dyn Any))));
         });
     }
 }
\ No newline at end of file
@@ -0,0 +1,131 @@
+use super::{Entity, World};
+use super::component::Component;
+use super::prelude::any::{Any, TypeId};
+use super::prelude::fmt;
+use super::prelude::{Box, HashMap, String};
+
+/// Component types registered by name with `World::register_named`, kept as
+/// one of the world's resources
+#[derive(Default)]
+struct ComponentNames(HashMap<String, NamedComponent>);
+
+struct NamedComponent {
+    ty: TypeId,
+    construct: fn() -> Box<dyn Any>
+}
+
+fn construct<T: Default + Any>() -> Box<dyn Any> {
+    Box::new(T::default())
+}
+
+/// Returned when a component is looked up by a name that hasn't been
+/// registered with `World::register_named`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownComponent(pub String);
+
+impl fmt::Display for UnknownComponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no component registered as {:?}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for UnknownComponent {}
+
+impl World {
+    /// Registers component type `T` under `name`, so tools and data files can
+    /// refer to it without knowing the type. Registering another type under the
+    /// same name replaces the first.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// #[derive(Default)]
+    /// struct Health(u32);
+    /// 
+    /// let mut world = World::new();
+    /// world.register_named::<Health>("health");
+    /// 
+    /// let ent = world.spawn_from_names(&["health"]).unwrap();
+    /// assert!(world.has_component::<Health>(ent));
+    /// assert!(world.spawn_from_names(&["mana"]).is_err());
+    /// ```
+    pub fn register_named<T: Component + Default>(&mut self, name: &str) {
+        self.record_size::<T>();
+        if self.get_resource::<ComponentNames>().is_none() {
+            self.insert_resource(ComponentNames::default());
+        }
+
+        let names = self.get_resource_mut::<ComponentNames>().unwrap();
+        names.0.insert(name.into(), NamedComponent {
+            ty: TypeId::of::<T>(),
+            construct: construct::<T>
+        });
+    }
+
+    /// The type of the component registered under `name`
+    pub fn named_component(&self, name: &str) -> Option<TypeId> {
+        self.named(name).map(|named| named.ty)
+    }
+
+    /// Adds a default-constructed component of the type registered under `name`
+    /// to entity `ent`, replacing any it already has. Returns whether or not the
+    /// entity is valid.
+    pub fn add_named_component(&self, ent: Entity, name: &str) -> Result<bool, UnknownComponent> {
+        let named = self.named(name).ok_or_else(|| UnknownComponent(name.into()))?;
+        Ok(self.add_component_dyn(ent, named.ty, (named.construct)()))
+    }
+
+    /// Creates an entity with a default-constructed component for each of
+    /// `names`. No entity is created if any of the names is unknown.
+    pub fn spawn_from_names(&mut self, names: &[&str]) -> Result<Entity, UnknownComponent> {
+        if let Some(unknown) = names.iter().find(|name| self.named(name).is_none()) {
+            return Err(UnknownComponent((*unknown).into()));
+        }
+
+        let ent = self.create_entity();
+        for name in names {
+            self.add_named_component(ent, name)?;
+        }
+
+        Ok(ent)
+    }
+
+    fn named(&self, name: &str) -> Option<&NamedComponent> {
+        self.get_resource::<ComponentNames>().and_then(|names| names.0.get(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_from_names() {
+        #[derive(Default)]
+        struct Position(f32, f32);
+        #[derive(Default)]
+        struct Health(u32);
+
+        let mut world = World::new();
+        world.register_named::<Position>("position");
+        world.register_named::<Health>("health");
+        assert_eq!(world.named_component("health"), Some(TypeId::of::<Health>()));
+
+        let ent = world.spawn_from_names(&["position", "health"]).unwrap();
+        let pos = world.get_component_ref::<Position>(ent).unwrap();
+        assert_eq!((pos.0, pos.1), (0.0, 0.0));
+        drop(pos);
+        assert_eq!(world.get_component_ref::<Health>(ent).unwrap().0, 0);
+
+        let before = world.iter_entities().count();
+        let err = world.spawn_from_names(&["health", "velocity"]).unwrap_err();
+        assert_eq!(err, UnknownComponent("velocity".into()));
+        assert_eq!(err.to_string(), "no component registered as \"velocity\"");
+        assert_eq!(world.iter_entities().count(), before);
+
+        world.remove_entity(ent);
+        assert_eq!(world.add_named_component(ent, "health"), Ok(false));
+    }
+}
@@ -0,0 +1,181 @@
+use std::any::TypeId;
+use std::cell::Cell;
+use std::ops::Range;
+use std::ptr;
+
+/// The component types a system has declared it reads and writes
+pub struct Access {
+    pub reads: Vec<TypeId>,
+    /// `None` if the system may write to any component
+    pub writes: Option<Vec<TypeId>>
+}
+
+impl Access {
+    /// Whether running systems with these two access sets at the same time
+    /// could let one observe or clobber the other's writes.
+    pub fn conflicts(&self, other: &Access) -> bool {
+        match (&self.writes, &other.writes) {
+            (Some(writes), Some(other_writes)) => {
+                writes.iter().any(|ty| other.reads.contains(ty) || other_writes.contains(ty)) ||
+                    other_writes.iter().any(|ty| self.reads.contains(ty))
+            },
+            _ => true
+        }
+    }
+
+    fn allows_read(&self, ty: TypeId) -> bool {
+        self.reads.contains(&ty) || self.allows_write(ty)
+    }
+
+    fn allows_write(&self, ty: TypeId) -> bool {
+        self.writes.as_ref().is_none_or(|writes| writes.contains(&ty))
+    }
+}
+
+thread_local! {
+    /// The access declared by the system `World::process_parallel` is running
+    /// on this thread, or null if it isn't running one
+    static RUNNING: Cell<*const Access> = const { Cell::new(ptr::null()) };
+}
+
+/// Restores the access of the system that was running before, even if the
+/// system running in its place panics
+struct Restore(*const Access);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        RUNNING.with(|running| running.set(self.0));
+    }
+}
+
+/// Runs `f` as a system with the declared `access`, so that in debug builds the
+/// `check_` functions can catch it stepping outside of it.
+#[inline]
+pub fn with_access<R, F>(access: &Access, f: F) -> R where F: FnOnce() -> R {
+    if !cfg!(debug_assertions) {
+        return f();
+    }
+
+    let _restore = Restore(RUNNING.with(|running| running.replace(access)));
+    f()
+}
+
+/// The access declared by the system running on this thread, if any
+fn running<'a>() -> Option<&'a Access> {
+    // Only set by `with_access`, which outlives any call from within `f`
+    RUNNING.with(|running| unsafe { running.get().as_ref() })
+}
+
+/// Panics if a system is running on this thread without declaring that it
+/// reads or writes components of type `ty`, named `name`.
+pub fn check_read(ty: TypeId, name: &str) {
+    if let Some(access) = running() {
+        assert!(access.allows_read(ty), "system read {} without declaring it in `reads` or `writes`", name);
+    }
+}
+
+/// Panics if a system is running on this thread without declaring that it
+/// writes components of type `ty`, named `name`.
+pub fn check_write(ty: TypeId, name: &str) {
+    if let Some(access) = running() {
+        assert!(access.allows_write(ty), "system wrote {} without declaring it in `writes`", name);
+    }
+}
+
+/// Panics if a system is running on this thread, as they can't add or remove
+/// entities or components.
+pub fn check_structural() {
+    assert!(running().is_none(), "systems run by `process_parallel` can't add or remove entities or components");
+}
+
+/// Splits systems, in registration order, into consecutive batches in which no
+/// two systems conflict, so that each batch can be run concurrently without
+/// reordering conflicting systems.
+pub fn batches<'a, I>(accesses: I) -> Vec<Range<usize>> where I: IntoIterator<Item = &'a Access> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<&Access> = Vec::new();
+    let mut start = 0;
+
+    for (i, access) in accesses.into_iter().enumerate() {
+        if batch.iter().any(|other| other.conflicts(access)) {
+            batches.push(start..i);
+            batch.clear();
+            start = i;
+        }
+
+        batch.push(access);
+    }
+
+    if !batch.is_empty() {
+        batches.push(start..start + batch.len());
+    }
+
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct A;
+    struct B;
+    struct C;
+
+    fn access(reads: Vec<TypeId>, writes: Option<Vec<TypeId>>) -> Access {
+        Access { reads, writes }
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let reads_a = access(vec!(TypeId::of::<A>()), Some(vec!()));
+        let writes_a = access(vec!(), Some(vec!(TypeId::of::<A>())));
+        let writes_b = access(vec!(TypeId::of::<A>()), Some(vec!(TypeId::of::<B>())));
+        let anything = access(vec!(), None);
+
+        assert!(!reads_a.conflicts(&reads_a));
+        assert!(reads_a.conflicts(&writes_a));
+        assert!(writes_a.conflicts(&reads_a));
+        assert!(writes_a.conflicts(&writes_b));
+        assert!(!reads_a.conflicts(&writes_b));
+        assert!(anything.conflicts(&reads_a));
+        assert!(reads_a.conflicts(&anything));
+    }
+
+    #[test]
+    fn test_batches() {
+        let accesses = vec!(
+            access(vec!(), Some(vec!(TypeId::of::<A>()))),
+            access(vec!(), Some(vec!(TypeId::of::<B>()))),
+            access(vec!(TypeId::of::<B>()), Some(vec!(TypeId::of::<C>()))),
+            access(vec!(TypeId::of::<A>()), Some(vec!())),
+            access(vec!(), None),
+            access(vec!(), None)
+        );
+
+        assert_eq!(batches(&accesses), vec!(0..2, 2..4, 4..5, 5..6));
+        assert_eq!(batches(&Vec::new()), Vec::new());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_checks() {
+        let access = access(vec!(TypeId::of::<A>()), Some(vec!(TypeId::of::<B>())));
+        check_write(TypeId::of::<C>(), "C");
+        check_structural();
+
+        with_access(&access, || {
+            check_read(TypeId::of::<A>(), "A");
+            check_read(TypeId::of::<B>(), "B");
+            check_write(TypeId::of::<B>(), "B");
+        });
+        let write_a = ::std::panic::catch_unwind(|| with_access(&access, || check_write(TypeId::of::<A>(), "A")));
+        assert!(write_a.is_err());
+        let read_c = ::std::panic::catch_unwind(|| with_access(&access, || check_read(TypeId::of::<C>(), "C")));
+        assert!(read_c.is_err());
+        let structural = ::std::panic::catch_unwind(|| with_access(&access, check_structural));
+        assert!(structural.is_err());
+
+        // Each panic left the thread as it found it
+        check_structural();
+    }
+}
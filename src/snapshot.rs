@@ -0,0 +1,390 @@
+use super::{Entity, FrameContext, World};
+use super::component::Component;
+use super::hierarchy::{Children, Parent};
+use super::prelude::any::{Any, TypeId};
+use super::prelude::fmt;
+use super::prelude::{Box, HashMap, Vec};
+
+/// How to copy components of the types registered with
+/// `World::register_snapshot`, kept as one of the world's resources
+#[derive(Clone, Default)]
+struct Snapshotters(HashMap<TypeId, Snapshotter>);
+
+/// How to copy the resources registered with `World::register_clone_resource`
+/// into another world, kept as one of the world's resources
+#[derive(Clone, Default)]
+struct ResourceCloners(HashMap<TypeId, fn(&World, &mut World)>);
+
+#[derive(Clone, Copy)]
+struct Snapshotter {
+    /// Copies an entity's component out of the world
+    capture: fn(&World, Entity) -> Option<Box<dyn Any>>,
+    /// Copies a captured component so a snapshot can be restored more than once
+    copy: fn(&dyn Any) -> Box<dyn Any>,
+    /// Registers the type as dense in a world cloned from one where it is
+    dense: fn(&mut World)
+}
+
+fn capture<T: Component + Clone>(world: &World, ent: Entity) -> Option<Box<dyn Any>> {
+    world.get_component_ref::<T>(ent).map(|component| Box::new((*component).clone()) as Box<dyn Any>)
+}
+
+fn copy<T: Component + Clone>(component: &dyn Any) -> Box<dyn Any> {
+    Box::new(component.downcast_ref::<T>().unwrap().clone())
+}
+
+fn dense<T: Component>(world: &mut World) {
+    world.register_dense_component::<T>();
+}
+
+fn clone_resource<T: Any + Clone>(src: &World, dst: &mut World) {
+    if let Some(resource) = src.get_resource::<T>() {
+        dst.insert_resource(resource.clone());
+    }
+}
+
+/// Why `World::try_clone` couldn't copy a world
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloneError {
+    /// The entity holds a component of a type that wasn't registered with
+    /// `World::register_snapshot`
+    UnregisteredComponent {
+        entity: Entity,
+        ty: TypeId
+    }
+}
+
+impl fmt::Display for CloneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CloneError::UnregisteredComponent { entity, ty } => {
+                write!(f, "entity {} holds a {:?}, which isn't registered for cloning", entity, ty)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for CloneError {}
+
+/// Copies of one entity's components, taken by `World::snapshot_entity`
+pub struct EntitySnapshot {
+    components: Vec<(TypeId, Box<dyn Any>)>,
+    skipped: Vec<TypeId>
+}
+
+impl EntitySnapshot {
+    /// The types of the components captured, in the order the entity held them
+    pub fn types(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.components.iter().map(|&(ty, _)| ty)
+    }
+
+    /// The types of the entity's components that weren't registered with
+    /// `World::register_snapshot`, and so were left out of the snapshot
+    pub fn skipped(&self) -> &[TypeId] {
+        &self.skipped
+    }
+}
+
+impl fmt::Debug for EntitySnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EntitySnapshot")
+            .field("components", &self.types().collect::<Vec<_>>())
+            .field("skipped", &self.skipped)
+            .finish()
+    }
+}
+
+impl World {
+    /// Allows components of type `T` to be captured by `snapshot_entity` and
+    /// copied by `try_clone`, copying them with `Clone`
+    pub fn register_snapshot<T: Component + Clone>(&mut self) {
+        self.record_size::<T>();
+        if self.get_resource::<Snapshotters>().is_none() {
+            self.insert_resource(Snapshotters::default());
+        }
+
+        let snapshotters = self.get_resource_mut::<Snapshotters>().unwrap();
+        snapshotters.0.insert(TypeId::of::<T>(), Snapshotter {
+            capture: capture::<T>,
+            copy: copy::<T>,
+            dense: dense::<T>
+        });
+    }
+
+    /// Allows the world's resource of type `T` to be copied by `try_clone`.
+    /// Resources that aren't registered are left out of the copy.
+    pub fn register_clone_resource<T: Any + Clone>(&mut self) {
+        if self.get_resource::<ResourceCloners>().is_none() {
+            self.insert_resource(ResourceCloners::default());
+        }
+
+        let cloners = self.get_resource_mut::<ResourceCloners>().unwrap();
+        cloners.0.insert(TypeId::of::<T>(), clone_resource::<T>);
+    }
+
+    /// Copies the whole world, for simulating ahead without disturbing it. Every
+    /// live entity keeps its ID in the copy, with copies of its components, and
+    /// IDs are reused in the same order. Resources registered with
+    /// `register_clone_resource` are copied along with `FrameContext`, and the
+    /// copy can itself be cloned.
+    /// 
+    /// Systems and `on_despawn` hooks aren't copied, so the copy has none until
+    /// they're registered with it again.
+    /// 
+    /// Fails, copying nothing, if any live entity holds a component whose type
+    /// wasn't registered with `register_snapshot`.
+    /// 
+    /// # Panics
+    /// Panics if any component is mutably borrowed.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// #[derive(Clone)]
+    /// struct Position(f32, f32);
+    /// 
+    /// let mut world = World::new();
+    /// world.register_snapshot::<Position>();
+    /// let ent = world.spawn().add(Position(0.0, 0.0)).id();
+    /// 
+    /// let lookahead = world.try_clone().unwrap();
+    /// lookahead.get_component::<Position>(ent).unwrap().0 = 10.0;
+    /// assert_eq!(world.get_component_ref::<Position>(ent).unwrap().0, 0.0);
+    /// ```
+    pub fn try_clone(&self) -> Result<World, CloneError> {
+        for ent in self.iter_entities() {
+            for ty in self.components_of(ent) {
+                if self.snapshotter(ty).is_none() {
+                    return Err(CloneError::UnregisteredComponent { entity: ent, ty });
+                }
+            }
+        }
+
+        let mut world = self.empty_copy();
+        for ty in self.dense_types() {
+            if let Some(snapshotter) = self.snapshotter(ty) {
+                (snapshotter.dense)(&mut world);
+            }
+        }
+
+        if let Some(&frame) = self.get_resource::<FrameContext>() {
+            world.insert_resource(frame);
+        }
+        if let Some(snapshotters) = self.get_resource::<Snapshotters>() {
+            world.insert_resource(snapshotters.clone());
+        }
+        if let Some(cloners) = self.get_resource::<ResourceCloners>() {
+            for cloner in cloners.0.values() {
+                cloner(self, &mut world);
+            }
+            world.insert_resource(cloners.clone());
+        }
+
+        for ent in self.iter_entities() {
+            for ty in self.components_of(ent) {
+                let component = (self.snapshotter(ty).unwrap().capture)(self, ent).unwrap();
+                world.add_component_dyn(ent, ty, component);
+            }
+        }
+
+        Ok(world)
+    }
+
+    /// Copies every component of entity `ent` whose type has been registered
+    /// with `register_snapshot`, or returns `None` if `ent` is invalid. The
+    /// types of any other components are recorded as skipped.
+    /// 
+    /// # Panics
+    /// Panics if any of the components captured is mutably borrowed.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// #[derive(Clone)]
+    /// struct Position(f32, f32);
+    /// 
+    /// let mut world = World::new();
+    /// world.register_snapshot::<Position>();
+    /// let ent = world.spawn().add(Position(0.0, 0.0)).id();
+    /// 
+    /// let checkpoint = world.snapshot_entity(ent).unwrap();
+    /// world.get_component::<Position>(ent).unwrap().0 = 10.0;
+    /// 
+    /// world.restore_entity(ent, &checkpoint);
+    /// assert_eq!(world.get_component_ref::<Position>(ent).unwrap().0, 0.0);
+    /// ```
+    pub fn snapshot_entity(&self, ent: Entity) -> Option<EntitySnapshot> {
+        if !self.is_valid(ent) {
+            return None;
+        }
+
+        let mut snapshot = EntitySnapshot {
+            components: Vec::new(),
+            skipped: Vec::new()
+        };
+        for ty in self.components_of(ent) {
+            match self.snapshotter(ty) {
+                Some(snapshotter) => {
+                    let component = (snapshotter.capture)(self, ent).unwrap();
+                    snapshot.components.push((ty, component));
+                },
+                None => snapshot.skipped.push(ty)
+            }
+        }
+
+        Some(snapshot)
+    }
+
+    /// Replaces every component of entity `ent` with copies of those in
+    /// `snapshot`, which can be restored again later. Components the snapshot
+    /// skipped are removed too. Returns whether or not `ent` is valid.
+    /// 
+    /// The entity's `Parent` and `Children` are kept as they are rather than
+    /// restored, since the entities they name refer back to it.
+    /// 
+    /// # Panics
+    /// Panics if any of the entity's components is currently borrowed.
+    pub fn restore_entity(&self, ent: Entity, snapshot: &EntitySnapshot) -> bool {
+        if !self.is_valid(ent) {
+            return false;
+        }
+
+        let linked = |ty| ty == TypeId::of::<Parent>() || ty == TypeId::of::<Children>();
+        for ty in self.components_of(ent) {
+            if !linked(ty) {
+                self.remove_component_dyn(ent, ty);
+            }
+        }
+
+        for &(ty, ref component) in snapshot.components.iter() {
+            if linked(ty) {
+                continue;
+            }
+
+            let snapshotter = self.snapshotter(ty).unwrap();
+            self.add_component_dyn(ent, ty, (snapshotter.copy)(&**component));
+        }
+
+        true
+    }
+
+    fn snapshotter(&self, ty: TypeId) -> Option<Snapshotter> {
+        self.get_resource::<Snapshotters>().and_then(|snapshotters| snapshotters.0.get(&ty).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_restore() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Position(f32, f32);
+        #[derive(Clone, Debug, PartialEq)]
+        struct Inventory(Vec<&'static str>);
+        struct Cursor;
+
+        let mut world = World::new();
+        world.register_snapshot::<Position>();
+        world.register_snapshot::<Inventory>();
+
+        let ent = world.spawn()
+            .add(Position(1.0, 1.0))
+            .add(Inventory(vec!("key")))
+            .add(Cursor)
+            .id();
+
+        let snapshot = world.snapshot_entity(ent).unwrap();
+        assert_eq!(snapshot.types().collect::<Vec<_>>(), vec!(TypeId::of::<Position>(), TypeId::of::<Inventory>()));
+        assert_eq!(snapshot.skipped(), &[TypeId::of::<Cursor>()]);
+
+        world.get_component::<Position>(ent).unwrap().0 = 5.0;
+        world.get_component::<Inventory>(ent).unwrap().0.clear();
+        world.remove_component::<Inventory>(ent);
+
+        for _ in 0..2 {
+            assert!(world.restore_entity(ent, &snapshot));
+            assert_eq!(*world.get_component_ref::<Position>(ent).unwrap(), Position(1.0, 1.0));
+            assert_eq!(*world.get_component_ref::<Inventory>(ent).unwrap(), Inventory(vec!("key")));
+            assert!(!world.has_component::<Cursor>(ent));
+
+            world.get_component::<Position>(ent).unwrap().1 = 3.0;
+        }
+
+        world.drop_entity(ent);
+        assert!(world.snapshot_entity(ent).is_none());
+        assert!(!world.restore_entity(ent, &snapshot));
+    }
+
+    #[test]
+    fn test_restore_keeps_hierarchy() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Health(u32);
+
+        let mut world = World::new();
+        world.register_snapshot::<Health>();
+        world.register_snapshot::<Children>();
+
+        let parent = world.create_entity();
+        let child = world.create_entity();
+        let snapshot = world.snapshot_entity(child).unwrap();
+        let parent_snapshot = world.snapshot_entity(parent).unwrap();
+
+        world.add_component(child, Health(5));
+        assert!(world.set_parent(child, parent));
+        assert!(world.restore_entity(child, &snapshot));
+        assert!(world.restore_entity(parent, &parent_snapshot));
+
+        assert!(!world.has_component::<Health>(child));
+        assert_eq!(*world.get_component_ref::<Parent>(child).unwrap(), Parent(parent));
+        assert_eq!(*world.get_component_ref::<Children>(parent).unwrap(), Children(vec!(child)));
+    }
+
+    #[test]
+    fn test_try_clone() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Position(f32, f32);
+        #[derive(Clone, Debug, PartialEq)]
+        struct Score(u32);
+        #[derive(Clone)]
+        struct Seed(u64);
+        struct Cursor;
+
+        let mut world = World::new();
+        world.register_dense_component::<Position>();
+        world.register_snapshot::<Position>();
+        world.register_snapshot::<Score>();
+        world.register_clone_resource::<Seed>();
+        world.insert_resource(Seed(7));
+
+        let ents: Vec<Entity> = (0..4).map(|i| world.spawn().add(Position(i as f32, 0.0)).id()).collect();
+        world.add_component(ents[3], Score(10));
+        world.drop_entity(ents[1]);
+        world.remove_entity(ents[2]);
+
+        let clone = world.try_clone().unwrap();
+        assert_eq!(clone.iter_entities().collect::<Vec<_>>(), vec!(ents[0], ents[3]));
+        assert_eq!(clone.get_resource::<Seed>().unwrap().0, 7);
+        assert_eq!(clone.dense_types(), vec!(TypeId::of::<Position>()));
+
+        clone.get_component::<Position>(ents[3]).unwrap().1 = 5.0;
+        clone.get_component::<Score>(ents[3]).unwrap().0 += 1;
+        clone.remove_component::<Score>(ents[3]);
+        assert_eq!(*world.get_component_ref::<Position>(ents[3]).unwrap(), Position(3.0, 0.0));
+        assert_eq!(*world.get_component_ref::<Score>(ents[3]).unwrap(), Score(10));
+
+        // IDs are reused in the same order in both worlds
+        let mut clone = clone;
+        world.process();
+        assert_eq!(clone.create_entity(), world.create_entity());
+        assert_eq!(clone.create_entity(), world.create_entity());
+
+        world.add_component(ents[0], Cursor);
+        let err = world.try_clone().err();
+        assert_eq!(err, Some(CloneError::UnregisteredComponent { entity: ents[0], ty: TypeId::of::<Cursor>() }));
+    }
+}
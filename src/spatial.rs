@@ -0,0 +1,330 @@
+use super::{Entity, EntityQuery, Matchers, World, SystemHandle};
+use super::component::Component;
+use super::systems::IterativeSystem;
+use super::prelude::any::TypeId;
+use super::prelude::cell::RefCell;
+use super::prelude::marker::PhantomData;
+use super::prelude::mem;
+use super::prelude::{HashMap, Vec};
+
+/// A cell of a `SpatialGrid`, as the coordinates of its lowest corner divided
+/// by the grid's cell size
+type Cell = (i32, i32);
+
+/// Buckets entities into square cells by position, so entities near a point can
+/// be found without testing every entity in the world. Kept up to date during
+/// each tick by the system registered with `World::add_spatial_grid`, and read
+/// through `World::query_radius`.
+/// 
+/// Positions are those the entities had when the grid was last updated, so
+/// entities moved since then are found where they were.
+pub struct SpatialGrid {
+    cell_size: f32,
+    /// The component type positions are read from
+    ty: TypeId,
+    cells: RefCell<HashMap<Cell, Vec<Entity>>>,
+    positions: RefCell<HashMap<Entity, (f32, f32)>>,
+    /// The system keeping the grid up to date, unregistered if the grid is
+    /// replaced
+    system: SystemHandle
+}
+
+impl SpatialGrid {
+    fn new(cell_size: f32, ty: TypeId, system: SystemHandle) -> SpatialGrid {
+        SpatialGrid {
+            cell_size,
+            ty,
+            cells: RefCell::new(HashMap::new()),
+            positions: RefCell::new(HashMap::new()),
+            system
+        }
+    }
+
+    /// The length of each side of the grid's cells
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Number of entities in the grid, including any removed from the world
+    /// since it was last updated
+    pub fn len(&self) -> usize {
+        self.positions.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.borrow().is_empty()
+    }
+
+    /// Records `ent` as being at `pos`, moving it between cells if needed
+    pub fn update(&self, ent: Entity, pos: (f32, f32)) {
+        let cell = self.cell_of(pos);
+        let old = self.positions.borrow_mut().insert(ent, pos);
+
+        if let Some(old) = old {
+            let old_cell = self.cell_of(old);
+            if old_cell == cell {
+                return;
+            }
+
+            self.remove_from_cell(ent, old_cell);
+        }
+
+        self.cells.borrow_mut().entry(cell).or_default().push(ent);
+    }
+
+    /// Forgets `ent`, returning whether it was in the grid
+    pub fn remove(&self, ent: Entity) -> bool {
+        let old = self.positions.borrow_mut().remove(&ent);
+        match old {
+            Some(old) => {
+                self.remove_from_cell(ent, self.cell_of(old));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Lists the entities within `radius` of `center` as of the last update,
+    /// in ascending order
+    pub fn query_radius(&self, center: (f32, f32), radius: f32) -> Vec<Entity> {
+        let mut found = Vec::new();
+        if radius < 0.0 {
+            return found;
+        }
+
+        let (min_x, min_y) = self.cell_of((center.0 - radius, center.1 - radius));
+        let (max_x, max_y) = self.cell_of((center.0 + radius, center.1 + radius));
+        let cells = self.cells.borrow();
+        let positions = self.positions.borrow();
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                let ents = match cells.get(&(x, y)) {
+                    Some(ents) => ents,
+                    None => continue
+                };
+
+                for &ent in ents.iter() {
+                    let pos = positions[&ent];
+                    let (dx, dy) = (pos.0 - center.0, pos.1 - center.1);
+                    if dx * dx + dy * dy <= radius * radius {
+                        found.push(ent);
+                    }
+                }
+            }
+        }
+        found.sort_unstable();
+
+        found
+    }
+
+    /// Rekeys the grid by the entities' new IDs after they've been moved, as
+    /// listed in `moved`, forgetting any not listed
+    pub(crate) fn remap(&self, moved: &HashMap<Entity, Entity>) {
+        let old = mem::take(&mut *self.positions.borrow_mut());
+        self.cells.borrow_mut().clear();
+        for (ent, pos) in old {
+            if let Some(&new) = moved.get(&ent) {
+                self.update(new, pos);
+            }
+        }
+    }
+
+    fn cell_of(&self, pos: (f32, f32)) -> Cell {
+        (floor(pos.0 / self.cell_size), floor(pos.1 / self.cell_size))
+    }
+
+    fn remove_from_cell(&self, ent: Entity, cell: Cell) {
+        let mut cells = self.cells.borrow_mut();
+        let empty = match cells.get_mut(&cell) {
+            Some(ents) => {
+                ents.retain(|&e| e != ent);
+                ents.is_empty()
+            },
+            None => false
+        };
+
+        if empty {
+            cells.remove(&cell);
+        }
+    }
+}
+
+/// Rounds `v` down to a whole number, without relying on `std`
+fn floor(v: f32) -> i32 {
+    let truncated = v as i32;
+    if (truncated as f32) > v {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
+
+/// Updates the world's `SpatialGrid` with the position of every entity holding
+/// a `T`
+struct UpdateSpatialGrid<T> {
+    position: fn(&T) -> (f32, f32),
+    component: PhantomData<fn(&T)>
+}
+
+impl<T: Component> IterativeSystem for UpdateSpatialGrid<T> {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<T>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        let pos = match world.get_component_ref::<T>(ent) {
+            Some(component) => (self.position)(&component),
+            None => return
+        };
+
+        if let Some(grid) = world.get_resource::<SpatialGrid>() {
+            grid.update(ent, pos);
+        }
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec!(TypeId::of::<T>())
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<SpatialGrid>()))
+    }
+}
+
+impl World {
+    /// Adds a `SpatialGrid` resource with cells `cell_size` across, indexing
+    /// every entity holding a `T` by the position `position` reads from it, and
+    /// registers the system that keeps it up to date each tick. Replaces any
+    /// grid the world already had, unregistering the system that updated it.
+    /// Entities are removed from the grid as they're despawned.
+    /// 
+    /// With the `parallel` feature, systems calling `query_radius` should list
+    /// `SpatialGrid` among their `reads` so they don't run alongside the update.
+    /// 
+    /// # Panics
+    /// Panics if `cell_size` isn't positive.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// struct Position(f32, f32);
+    /// 
+    /// let mut world = World::new();
+    /// world.add_spatial_grid(10.0, |pos: &Position| (pos.0, pos.1));
+    /// 
+    /// let near = world.create_entity();
+    /// world.add_component(near, Position(1.0, 1.0));
+    /// let far = world.create_entity();
+    /// world.add_component(far, Position(50.0, 50.0));
+    /// 
+    /// world.process();
+    /// assert_eq!(world.query_radius((0.0, 0.0), 5.0), vec!(near));
+    /// ```
+    pub fn add_spatial_grid<T: Component>(&mut self, cell_size: f32, position: fn(&T) -> (f32, f32)) -> SystemHandle {
+        assert!(cell_size > 0.0, "spatial grid cells must have a positive size");
+
+        let system = self.register_iterative_system(UpdateSpatialGrid {
+            position,
+            component: PhantomData
+        });
+        match self.insert_resource(SpatialGrid::new(cell_size, TypeId::of::<T>(), system)) {
+            Some(old) => {
+                self.unregister_system(old.system);
+            },
+            None => self.on_despawn(|ent, world| {
+                if let Some(grid) = world.get_resource::<SpatialGrid>() {
+                    grid.remove(ent);
+                }
+            })
+        }
+
+        system
+    }
+
+    /// Lists the live entities within `radius` of `center`, in ascending order,
+    /// by their positions as of the last update of the world's `SpatialGrid`.
+    /// Entities that have lost the grid's position component are left out.
+    /// Empty if the world has no grid.
+    pub fn query_radius(&self, center: (f32, f32), radius: f32) -> Vec<Entity> {
+        match self.get_resource::<SpatialGrid>() {
+            Some(grid) => {
+                let mut found = grid.query_radius(center, radius);
+                found.retain(|&ent| self.has_components(ent, &[grid.ty]));
+                found
+            },
+            None => Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_radius() {
+        struct Position(f32, f32);
+
+        let mut world = World::new();
+        assert!(world.query_radius((0.0, 0.0), 100.0).is_empty());
+        world.add_spatial_grid(4.0, |pos: &Position| (pos.0, pos.1));
+
+        let mut spawn = |x, y| {
+            let ent = world.create_entity();
+            world.add_component(ent, Position(x, y));
+            ent
+        };
+        let origin = spawn(0.0, 0.0);
+        let close = spawn(-2.5, 2.5);
+        let edge = spawn(5.0, 0.0);
+        let far = spawn(30.0, -30.0);
+        spawn(4.0, 4.0);
+
+        world.process();
+        assert_eq!(world.query_radius((0.0, 0.0), 5.0), vec!(origin, close, edge));
+        assert_eq!(world.query_radius((29.0, -29.0), 2.0), vec!(far));
+        assert!(world.query_radius((0.0, 0.0), -1.0).is_empty());
+
+        // Moves are picked up by the next tick, and despawned entities dropped
+        world.get_component::<Position>(far).unwrap().0 = 1.0;
+        world.get_component::<Position>(far).unwrap().1 = -1.0;
+        world.drop_entity(close);
+        world.process();
+        assert_eq!(world.query_radius((0.0, 0.0), 5.0), vec!(origin, edge, far));
+        assert_eq!(world.get_resource::<SpatialGrid>().unwrap().len(), 4);
+
+        world.remove_component::<Position>(edge);
+        assert_eq!(world.query_radius((0.0, 0.0), 5.0), vec!(origin, far));
+
+        let moved = world.defragment();
+        assert_eq!(world.query_radius((0.0, 0.0), 5.0), vec!(moved[&origin], moved[&far]));
+    }
+
+    #[test]
+    fn test_replace_spatial_grid() {
+        struct Position(f32, f32);
+
+        let mut world = World::new();
+        let first = world.add_spatial_grid(4.0, |pos: &Position| (pos.0, pos.1));
+        let second = world.add_spatial_grid(8.0, |pos: &Position| (pos.0, pos.1));
+        assert_eq!(world.system_count(), 1);
+        assert!(world.system_name(first).is_none());
+        assert!(world.system_name(second).is_some());
+
+        let ent = world.create_entity();
+        world.add_component(ent, Position(1.0, 1.0));
+        world.process();
+        assert_eq!(world.get_resource::<SpatialGrid>().unwrap().cell_size(), 8.0);
+        assert_eq!(world.query_radius((0.0, 0.0), 2.0), vec!(ent));
+    }
+
+    #[test]
+    fn test_floor() {
+        assert_eq!(floor(1.5), 1);
+        assert_eq!(floor(-0.5), -1);
+        assert_eq!(floor(-2.0), -2);
+        assert_eq!(floor(0.0), 0);
+    }
+}
@@ -0,0 +1,78 @@
+use super::World;
+
+use std::sync::{Mutex, MutexGuard};
+
+/// A world that can be moved to, and shared between, threads.
+/// 
+/// `World` is neither `Send` nor `Sync`: it stores components, resources and
+/// systems as type-erased pointers, and tracks borrows and removals with
+/// `Cell` and `RefCell`. `SyncWorld` wraps a world in a single `Mutex`, so
+/// every operation, reads included, goes through `lock` and only one thread
+/// touches the world's storage at a time. There are no finer grained locks.
+/// 
+/// # Examples
+/// ```
+/// use apollo_ecs::*;
+/// use std::sync::Arc;
+/// use std::thread;
+/// 
+/// struct Score(u32);
+/// 
+/// let mut world = World::new();
+/// let ent = world.create_entity();
+/// world.add_component(ent, Score(0));
+/// 
+/// // `Score` is `Send`, as is everything else in the world
+/// let world = Arc::new(unsafe { SyncWorld::new(world) });
+/// let worker = {
+///     let world = world.clone();
+///     thread::spawn(move || {
+///         world.lock().get_component::<Score>(ent).unwrap().0 += 1;
+///     })
+/// };
+/// worker.join().unwrap();
+/// 
+/// assert_eq!(world.lock().get_component_ref::<Score>(ent).unwrap().0, 1);
+/// ```
+pub struct SyncWorld {
+    world: Mutex<World>
+}
+
+// The mutex guarantees the world is only ever accessed from one thread at a
+// time, which is all its `Cell`s and `RefCell`s require. What it can't
+// guarantee is that the values stored in the world may be used from another
+// thread, which is left to the caller of `SyncWorld::new`.
+unsafe impl Send for SyncWorld {}
+unsafe impl Sync for SyncWorld {}
+
+impl SyncWorld {
+    /// Wraps `world` so it can be sent to and shared between threads.
+    /// 
+    /// # Safety
+    /// The world stores its contents without their types, so it can't check
+    /// them itself. The caller must guarantee that every component, resource
+    /// and system in `world`, and every one added later through `lock`, is
+    /// `Send`. Guards returned by `get_component` and `get_component_ref` must
+    /// not outlive the lock they were taken under, which the borrow checker
+    /// already enforces.
+    pub unsafe fn new(world: World) -> SyncWorld {
+        SyncWorld {
+            world: Mutex::new(world)
+        }
+    }
+
+    /// Locks the world for exclusive access from the current thread, blocking
+    /// until any other thread holding it releases the lock.
+    /// 
+    /// # Panics
+    /// Panics if another thread panicked while holding the lock, since the
+    /// world may have been left half way through a tick.
+    pub fn lock(&self) -> MutexGuard<'_, World> {
+        self.world.lock().expect("world lock poisoned")
+    }
+
+    /// Unwraps the world, for use on the current thread only
+    pub fn into_inner(self) -> World {
+        self.world.into_inner().expect("world lock poisoned")
+    }
+}
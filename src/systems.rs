@@ -1,5 +1,8 @@
 use super::{Entity, EntityQuery, World};
 
+use super::prelude::any::{self, TypeId};
+use super::prelude::Vec;
+
 /// An `IterativeSystem` iterates over all entities matching its
 /// provided `EntityQuery` on every world tick.
 pub trait IterativeSystem {
@@ -8,9 +11,163 @@ pub trait IterativeSystem {
     /// to this system
     fn get_query() -> EntityQuery where Self: Sized;
 
-    // TODO: Shound't take an EntityEditor
     /// The main loop for this system, `process` is called
     /// for every entity that matches this system's query
     /// on every world tick.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// use apollo_ecs::systems::IterativeSystem;
+    /// 
+    /// struct Age(u32);
+    /// struct Aging;
+    /// 
+    /// impl IterativeSystem for Aging {
+    ///     fn get_query() -> EntityQuery {
+    ///         EntityQuery::new(Matchers::with::<Age>())
+    ///     }
+    /// 
+    ///     fn process(&mut self, ent: Entity, world: &World) {
+    ///         world.get_component::<Age>(ent).unwrap().0 += 1;
+    ///     }
+    /// }
+    /// 
+    /// let mut world = World::new();
+    /// world.register_iterative_system(Aging);
+    /// let ent = world.create_entity();
+    /// world.add_component(ent, Age(0));
+    /// 
+    /// world.process();
+    /// assert_eq!(world.get_component_ref::<Age>(ent).unwrap().0, 1);
+    /// ```
     fn process(&mut self, ent: Entity, world: &World);
-}
\ No newline at end of file
+
+    /// Static method to return the component types this system
+    /// reads. Only consulted when the system also declares
+    /// its `writes`.
+    fn reads() -> Vec<TypeId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// Static method to return the component types this system
+    /// writes, or `None` if it may write any component. Systems
+    /// that declare what they access can be run alongside the
+    /// systems they don't conflict with by `World::process_parallel`.
+    fn writes() -> Option<Vec<TypeId>> where Self: Sized {
+        None
+    }
+
+    /// A human-readable name for the system, used in `TickStats`.
+    /// Defaults to the system's type name.
+    fn name(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+
+    /// Called with the world the system is being registered with,
+    /// before it first runs, to set up any resources it needs.
+    fn on_register(&mut self, _world: &mut World) {}
+
+    /// Called with the world once the system has been removed
+    /// from it by `World::unregister_system`, to tear down what
+    /// `on_register` set up.
+    fn on_unregister(&mut self, _world: &mut World) {}
+}
+/// A `PairwiseSystem` visits every unordered pair of distinct entities
+/// matching its provided `EntityQuery` on every world tick, for
+/// interactions such as collisions. The number of pairs grows with
+/// the square of the number of matched entities.
+pub trait PairwiseSystem {
+    /// Static method to return the query that will be used
+    /// to filter the world's entities before they are paired
+    /// up for this system
+    fn get_query() -> EntityQuery where Self: Sized;
+
+    /// Called once for each pair of entities matching this
+    /// system's query on every world tick. Each pair is visited
+    /// once, with `a` ordered before `b` in the matched set.
+    fn process(&mut self, a: Entity, b: Entity, world: &World);
+
+    /// Static method to return the component types this system
+    /// reads, as for `IterativeSystem::reads`.
+    fn reads() -> Vec<TypeId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// Static method to return the component types this system
+    /// writes, as for `IterativeSystem::writes`.
+    fn writes() -> Option<Vec<TypeId>> where Self: Sized {
+        None
+    }
+
+    /// A human-readable name for the system, as for
+    /// `IterativeSystem::name`.
+    fn name(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+}
+
+/// A `ParallelSystem` is called for every entity matching its provided
+/// `EntityQuery` on every world tick, like an `IterativeSystem`, but only
+/// through a shared reference. `World::process_parallel` splits its matched
+/// entities across a thread pool; `World::process` visits them in turn.
+pub trait ParallelSystem: Send + Sync {
+    /// Static method to return the query that will be used
+    /// to filter the world's entities before they are passed
+    /// to this system
+    fn get_query() -> EntityQuery where Self: Sized;
+
+    /// Called for every entity that matches this system's query
+    /// on every world tick, possibly from several threads at once.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// use apollo_ecs::systems::ParallelSystem;
+    /// use std::any::TypeId;
+    /// 
+    /// struct Age(u32);
+    /// struct Aging;
+    /// 
+    /// impl ParallelSystem for Aging {
+    ///     fn get_query() -> EntityQuery {
+    ///         EntityQuery::new(Matchers::with::<Age>())
+    ///     }
+    /// 
+    ///     fn process(&self, ent: Entity, world: &World) {
+    ///         world.get_component::<Age>(ent).unwrap().0 += 1;
+    ///     }
+    /// 
+    ///     fn writes() -> Option<Vec<TypeId>> {
+    ///         Some(vec!(TypeId::of::<Age>()))
+    ///     }
+    /// }
+    /// 
+    /// let mut world = World::new();
+    /// world.register_parallel_system(Aging);
+    /// let ent = world.create_entity();
+    /// world.add_component(ent, Age(0));
+    /// 
+    /// world.process();
+    /// assert_eq!(world.get_component_ref::<Age>(ent).unwrap().0, 1);
+    /// ```
+    fn process(&self, ent: Entity, world: &World);
+
+    /// Static method to return the component types this system
+    /// reads, as for `IterativeSystem::reads`.
+    fn reads() -> Vec<TypeId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// Static method to return the component types this system
+    /// writes, as for `IterativeSystem::writes`.
+    fn writes() -> Option<Vec<TypeId>> where Self: Sized {
+        None
+    }
+
+    /// A human-readable name for the system, as for
+    /// `IterativeSystem::name`.
+    fn name(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+}
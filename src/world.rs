@@ -1,43 +1,767 @@
 use super::Entity;
-use super::query::{Query, Condition};
-use super::systems::IterativeSystem;
+use super::archetype::Archetypes;
+use super::borrow::{BorrowFlag, ChangeFlag, ChangeList, ComponentRef, ComponentMut};
+use super::bundle::Bundle;
+use super::column::{Column, DenseColumn};
+use super::component;
+use super::hierarchy::{Parent, Children};
+use super::query::{Query, QueryBuilder, Matchers, Condition, MatchedEntities};
+#[cfg(feature = "parallel")]
+use super::schedule::{self, Access};
+#[cfg(feature = "parallel")]
+use rayon::{self, prelude::*};
+use super::spatial::SpatialGrid;
+use super::systems::{IterativeSystem, PairwiseSystem, ParallelSystem};
 
-use std::cell::RefCell;
-use std::collections::VecDeque;
-use std::any::{Any, TypeId};
+use super::prelude::cell::{Cell, RefCell};
+use super::prelude::{Box, HashMap, String, Vec, VecDeque};
+use super::prelude::any::{self, Any, TypeId};
+use super::prelude::atomic::{AtomicUsize, Ordering};
+use super::prelude::fmt;
+use super::prelude::mem;
 
-pub type Components = Vec<Component>;
-pub type Component = (TypeId, *mut Any);
+pub type Component = (TypeId, *mut dyn Any);
+
+/// Past this many components an entity's storage builds a `TypeId` index
+/// rather than scanning its component list on every lookup.
+const INDEX_THRESHOLD: usize = 8;
+
+/// The components held by a single entity. Small entities are stored as a
+/// plain list that is scanned on lookup; once an entity holds more than
+/// `INDEX_THRESHOLD` components an index is kept alongside the list so lookups
+/// stay O(1).
+pub struct Components {
+    list: Vec<Slot>,
+    index: Option<HashMap<TypeId, usize>>,
+    /// Components recently added or removed, which most entities don't have
+    events: Option<Box<Events>>,
+    /// Whether the entity is on its world's list of changed entities
+    listed: ChangeFlag
+}
+
+/// A component along with the state tracked for it, kept together so that
+/// looking up a component touches as little memory as possible
+struct Slot {
+    component: Component,
+    borrow: BorrowFlag,
+    /// Whether the component was added or mutably borrowed this tick
+    changed: ChangeFlag
+}
+
+/// Components added to or removed from an entity over the last two ticks
+#[derive(Default)]
+struct Events {
+    /// Recorded during the previous tick, and visible to queries in this one
+    visible: Vec<(TypeId, Event)>,
+    /// Recorded during this tick, to become visible in the next one
+    pending: Vec<(TypeId, Event)>
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Event {
+    Added,
+    Removed
+}
+
+impl Components {
+    pub fn with_capacity(capacity: usize) -> Components {
+        Components {
+            list: Vec::with_capacity(capacity),
+            index: None,
+            events: None,
+            listed: ChangeFlag::default()
+        }
+    }
+
+    /// Find the position in the list of the component stored for type `ty`
+    pub(crate) fn position(&self, ty: TypeId) -> Option<usize> {
+        match self.index {
+            Some(ref index) => index.get(&ty).cloned(),
+            None => self.list.iter().position(|slot| slot.component.0 == ty)
+        }
+    }
+
+    /// Find the component pointer stored for type `ty`
+    #[inline]
+    pub fn get(&self, ty: TypeId) -> Option<*mut dyn Any> {
+        self.position(ty).map(|i| self.ptr(i))
+    }
+
+    /// The pointer to the component at position `i`
+    #[inline]
+    pub(crate) fn ptr(&self, i: usize) -> *mut dyn Any {
+        self.list[i].component.1
+    }
+
+    /// The borrow flag of the component at position `i`
+    #[inline]
+    pub(crate) fn flag(&self, i: usize) -> &BorrowFlag {
+        &self.list[i].borrow
+    }
+
+    /// Whether the component of type `ty` was added or mutably borrowed this tick
+    pub fn changed(&self, ty: TypeId) -> bool {
+        self.position(ty).is_some_and(|i| self.list[i].changed.get())
+    }
+
+    #[inline]
+    pub(crate) fn mark_changed(&self, i: usize) {
+        self.list[i].changed.set();
+    }
+
+    /// Records that the entity is on its world's list of changed entities,
+    /// returning `false` if it already was
+    #[inline]
+    pub(crate) fn mark_listed(&self) -> bool {
+        !self.listed.test_and_set()
+    }
+
+    pub(crate) fn clear_changed(&mut self) {
+        for slot in self.list.iter_mut() {
+            slot.changed.clear();
+        }
+        self.listed.clear();
+    }
+
+    /// Whether a component of type `ty` was added during the previous tick
+    pub fn added(&self, ty: TypeId) -> bool {
+        self.has_event(ty, Event::Added)
+    }
+
+    /// Whether a component of type `ty` was removed during the previous tick
+    pub fn removed(&self, ty: TypeId) -> bool {
+        self.has_event(ty, Event::Removed)
+    }
+
+    fn has_event(&self, ty: TypeId, event: Event) -> bool {
+        match self.events {
+            Some(ref events) => events.visible.contains(&(ty, event)),
+            None => false
+        }
+    }
+
+    #[inline]
+    pub(crate) fn has_events(&self) -> bool {
+        self.events.is_some()
+    }
+
+    /// Records that a component of type `ty` was added or removed, returning
+    /// whether this is the first event recorded since the entity had none
+    pub(crate) fn record(&mut self, ty: TypeId, event: Event) -> bool {
+        let first = self.events.is_none();
+        self.events.get_or_insert_with(Default::default).pending.push((ty, event));
+
+        first
+    }
+
+    /// Makes the events recorded this tick visible, forgetting those from the
+    /// previous tick. Returns whether any events are left.
+    pub(crate) fn rotate_events(&mut self) -> bool {
+        let empty = match self.events {
+            Some(ref mut events) => {
+                events.visible = mem::take(&mut events.pending);
+                events.visible.is_empty()
+            },
+            None => return false
+        };
+
+        if empty {
+            self.events = None;
+        }
+
+        !empty
+    }
+
+    #[inline]
+    pub fn contains(&self, ty: TypeId) -> bool {
+        self.get(ty).is_some()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Iterates over every component, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = &Component> + '_ {
+        self.list.iter().map(|slot| &slot.component)
+    }
+
+    pub fn push(&mut self, component: Component) {
+        let i = self.list.len();
+        self.list.push(Slot {
+            component,
+            borrow: BorrowFlag::new(),
+            changed: ChangeFlag::new()
+        });
+
+        match self.index {
+            Some(ref mut index) => {
+                index.entry(component.0).or_insert(i);
+            },
+            None if self.list.len() > INDEX_THRESHOLD => {
+                // Going backwards leaves each type at its first position
+                let index = self.list.iter().enumerate().rev()
+                    .map(|(i, slot)| (slot.component.0, i))
+                    .collect();
+
+                self.index = Some(index);
+            },
+            None => ()
+        }
+    }
+
+    /// Stores `component`, replacing the pointer already stored for its type if
+    /// there is one. The replaced pointer is returned for the caller to free.
+    /// 
+    /// # Panics
+    /// Panics if the component being replaced is currently borrowed.
+    pub fn insert(&mut self, component: Component) -> Option<*mut dyn Any> {
+        match self.position(component.0) {
+            Some(i) => {
+                if self.list[i].borrow.is_borrowed() {
+                    panic!("component already borrowed");
+                }
+
+                self.mark_changed(i);
+                Some(mem::replace(&mut self.list[i].component.1, component.1))
+            },
+            None => {
+                self.push(component);
+                None
+            }
+        }
+    }
+
+    /// Forgets the component stored for type `ty`, returning its pointer for
+    /// the caller to free.
+    /// 
+    /// # Panics
+    /// Panics if the component, or any stored after it, is currently borrowed.
+    /// Borrows find their component by position, and removing a component
+    /// moves every later one back a place.
+    pub fn remove(&mut self, ty: TypeId) -> Option<*mut dyn Any> {
+        let i = self.position(ty)?;
+        if self.list[i].borrow.is_borrowed() {
+            panic!("component already borrowed");
+        }
+        if self.list[i + 1..].iter().any(|slot| slot.borrow.is_borrowed()) {
+            panic!("component removed while a later component of the entity is borrowed");
+        }
+
+        let slot = self.list.remove(i);
+        if let Some(ref mut index) = self.index {
+            index.remove(&ty);
+            for position in index.values_mut() {
+                if *position > i {
+                    *position -= 1;
+                }
+            }
+        }
+
+        Some(slot.component.1)
+    }
+
+    /// Forgets every component pointer without freeing them
+    pub fn clear(&mut self) {
+        self.list.truncate(0);
+        self.index = None;
+        self.events = None;
+    }
+}
+
+impl From<Vec<Component>> for Components {
+    fn from(list: Vec<Component>) -> Components {
+        let mut components = Components::with_capacity(list.len());
+        for component in list {
+            components.push(component);
+        }
+
+        components
+    }
+}
 
 /// The world contains all entities and their components and delegates
 /// their processing to systems.
 pub struct World {
+    /// Tells this world apart from others, so it can refuse their system handles
+    id: usize,
     pub(crate) entities: Vec<RefCell<Components>>,
-    valid_ents: Vec<bool>,
-    iterative_systems: Vec<(RefCell<Box<IterativeSystem>>, Query)>,
+    valid_ents: Vec<Cell<bool>>,
+    systems: Vec<SystemEntry>,
+    /// Names of the stages systems run in, in the order they run
+    stages: Vec<String>,
+    /// Indices into `systems` in the order they run: by stage, then in the
+    /// order they were registered
+    order: Vec<usize>,
+    startup_systems: Vec<SystemEntry>,
     free_ents: VecDeque<Entity>,
-    dead_ents: RefCell<VecDeque<Entity>>
+    dead_ents: RefCell<VecDeque<Entity>>,
+    /// Entities with components added or removed recently, whose events need
+    /// moving along at the start of each tick
+    evented_ents: RefCell<Vec<Entity>>,
+    /// Entities with components added or mutably borrowed this tick, whose
+    /// change flags need clearing at its end
+    changed_ents: ChangeList,
+    archetypes: RefCell<Archetypes>,
+    structure_version: Cell<u64>,
+    /// Number of calls to `apply` currently running
+    batch_depth: usize,
+    /// Whether the structure changed during `apply` without bumping the version
+    batched_change: Cell<bool>,
+    resources: HashMap<TypeId, Box<dyn Any>>,
+    /// Storage for the component types registered as dense
+    columns: RefCell<HashMap<TypeId, Box<dyn Column>>>,
+    /// Called with each entity about to be freed, registered by `on_despawn`
+    despawn_hooks: Vec<Box<DespawnHook>>,
+    /// Names of the component types added with `add_component`
+    #[cfg(feature = "debug-names")]
+    type_names: RefCell<HashMap<TypeId, &'static str>>,
+    /// Sizes of the component types registered with the world in any way, for
+    /// `component_memory_usage`
+    component_sizes: HashMap<TypeId, usize>,
+    growth: GrowthPolicy,
+    reuse: ReusePolicy
+}
+
+/// Name of the stage systems are registered in unless another is given, which
+/// runs before every stage added with `World::add_stage`
+pub const DEFAULT_STAGE: &str = "default";
+
+/// An inconsistency in a world's storage found by `World::validate`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorldError {
+    /// The entity holds a null pointer for its component of this type
+    NullComponent(Entity, TypeId),
+    /// The entity holds more than one component of this type
+    DuplicateComponent(Entity, TypeId),
+    /// The entity is live but its ID is waiting to be reused
+    LiveEntityFreed(Entity),
+    /// The world tracks storage for this many entities, but whether they're
+    /// live for a different number
+    LengthMismatch {
+        entities: usize,
+        valid: usize
+    }
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WorldError::NullComponent(ent, ty) => write!(f, "entity {} holds a null {:?}", ent, ty),
+            WorldError::DuplicateComponent(ent, ty) => write!(f, "entity {} holds more than one {:?}", ent, ty),
+            WorldError::LiveEntityFreed(ent) => write!(f, "entity {} is live but has been freed", ent),
+            WorldError::LengthMismatch { entities, valid } => {
+                write!(f, "storage for {} entities, but liveness for {}", entities, valid)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for WorldError {}
+
+/// Why `World::try_get_component` couldn't borrow a component
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentError {
+    /// The entity isn't live
+    InvalidEntity(Entity),
+    /// The entity has no component of the named type
+    ComponentMissing {
+        type_name: &'static str
+    },
+    /// The entity's component of the named type is already borrowed
+    AlreadyBorrowed {
+        type_name: &'static str
+    }
+}
+
+impl fmt::Display for ComponentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ComponentError::InvalidEntity(ent) => write!(f, "entity {} is not live", ent),
+            ComponentError::ComponentMissing { type_name } => write!(f, "entity has no {}", type_name),
+            ComponentError::AlreadyBorrowed { type_name } => write!(f, "{} is already borrowed", type_name)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for ComponentError {}
+
+/// Number of entities a world has room for when it's created, unless set with
+/// `WorldBuilder::initial_capacity`
+const DEFAULT_CAPACITY: usize = 131072;
+
+/// How a world's entity storage grows once it's full
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum GrowthPolicy {
+    /// Double the storage, so that growing is rare but the world may end up
+    /// with up to twice the room it needs
+    #[default]
+    Double,
+    /// Grow by room for this many more entities at a time
+    Fixed(usize),
+    /// Grow by room for one more entity at a time, never holding more than
+    /// needed but reallocating whenever an entity is created past the end
+    Exact
+}
+
+/// Which removed entity's ID `World::create_entity` hands out again first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ReusePolicy {
+    /// Reuse the ID that's been free the longest, spreading reuse evenly over
+    /// the freed IDs
+    #[default]
+    Fifo,
+    /// Reuse the most recently freed ID, whose storage is the most likely to
+    /// still be in cache
+    Lifo
+}
+
+/// Configures a `World` before it's created, as returned by `World::builder`.
+/// 
+/// # Examples
+/// ```
+/// use apollo_ecs::*;
+/// 
+/// let world = World::builder()
+///     .initial_capacity(1024)
+///     .growth(GrowthPolicy::Fixed(1024))
+///     .reuse(ReusePolicy::Lifo)
+///     .build();
+/// assert_eq!(world.capacity(), 1024);
+/// ```
+#[derive(Clone, Debug)]
+pub struct WorldBuilder {
+    capacity: usize,
+    growth: GrowthPolicy,
+    reuse: ReusePolicy
+}
+
+impl WorldBuilder {
+    /// Sets the number of entities the world has room for before its storage
+    /// first has to grow, 131072 by default. A third as many removed entities
+    /// can wait to have their IDs reused before that storage grows too.
+    pub fn initial_capacity(mut self, capacity: usize) -> WorldBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets how the world's entity storage grows once it's full, doubling by
+    /// default
+    pub fn growth(mut self, growth: GrowthPolicy) -> WorldBuilder {
+        self.growth = growth;
+        self
+    }
+
+    /// Sets which removed entity's ID is reused first when an entity is
+    /// created, the one that's been free the longest by default
+    pub fn reuse(mut self, reuse: ReusePolicy) -> WorldBuilder {
+        self.reuse = reuse;
+        self
+    }
+
+    /// Creates the configured world
+    pub fn build(self) -> World {
+        let capacity = self.capacity;
+        let mut world = World {
+            id: NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed),
+            entities: Vec::with_capacity(capacity),
+            systems: Vec::new(),
+            stages: vec!(String::from(DEFAULT_STAGE)),
+            order: Vec::new(),
+            startup_systems: Vec::new(),
+            free_ents: VecDeque::with_capacity(capacity / 3),
+            dead_ents: RefCell::new(VecDeque::with_capacity(capacity / 3)),
+            evented_ents: RefCell::new(Vec::new()),
+            changed_ents: ChangeList::default(),
+            valid_ents: Vec::with_capacity(capacity),
+            archetypes: RefCell::new(Archetypes::new()),
+            structure_version: Cell::new(0),
+            batch_depth: 0,
+            batched_change: Cell::new(false),
+            resources: HashMap::new(),
+            columns: RefCell::new(HashMap::new()),
+            despawn_hooks: Vec::new(),
+            #[cfg(feature = "debug-names")]
+            type_names: RefCell::new(HashMap::new()),
+            component_sizes: HashMap::new(),
+            growth: self.growth,
+            reuse: self.reuse
+        };
+        world.insert_resource(FrameContext::default());
+
+        world
+    }
+}
+
+impl Default for WorldBuilder {
+    fn default() -> WorldBuilder {
+        WorldBuilder {
+            capacity: DEFAULT_CAPACITY,
+            growth: GrowthPolicy::default(),
+            reuse: ReusePolicy::default()
+        }
+    }
+}
+
+/// Timing information for the current tick, kept up to date by the world
+/// as a resource.
+/// 
+/// # Examples
+/// ```
+/// use apollo_ecs::*;
+/// 
+/// let mut world = World::new();
+/// world.process_dt(0.016);
+/// 
+/// // `frame` has moved on to the next tick
+/// let ctx = world.get_resource::<FrameContext>().unwrap();
+/// assert_eq!(ctx.dt, 0.016);
+/// assert_eq!(ctx.frame, 1);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameContext {
+    /// Time elapsed since the previous tick
+    pub dt: f32,
+    /// Index of the current tick, starting from zero
+    pub frame: u64
+}
+
+/// Identifies a system registered with a world, as returned when it was
+/// registered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SystemHandle {
+    /// ID of the world the system was registered with
+    world: usize,
+    /// Index of the system in that world's `systems`
+    index: usize
+}
+
+/// ID given to the next world built
+static NEXT_WORLD_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts of the work done during a single tick, as returned by
+/// [`World::tick`](struct.World.html#method.tick)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TickStats {
+    /// Entities passed to systems, counting an entity once per system
+    pub entities_visited: usize,
+    /// Systems that ran, leaving out those disabled or skipped by their run
+    /// criteria
+    pub systems_run: usize,
+    /// Entities removed with `remove_entity` and dropped at the end of the tick
+    pub entities_despawned: usize,
+    /// The name of each system that ran and the entities it visited, in the
+    /// order they ran
+    pub per_system: Vec<(SystemHandle, &'static str, usize)>
+}
+
+/// A registered system along with its query and the entities
+/// that query matched when the system last ran.
+struct SystemEntry {
+    system: RefCell<System>,
+    /// Type of the boxed system, for looking it up again
+    ty: TypeId,
+    /// Name of the boxed system's type, for the spans it runs in
+    #[cfg(feature = "tracing")]
+    type_name: &'static str,
+    /// Decides each tick whether the system runs at all
+    run_criteria: Option<Box<RunCriteria>>,
+    /// Index of the stage the system runs in
+    stage: usize,
+    enabled: bool,
+    query: Query,
+    #[cfg(feature = "parallel")]
+    access: Access,
+    matches: RefCell<MatchCache>
+}
+
+/// Decides whether a system runs on a given tick
+type RunCriteria = dyn Fn(&World) -> bool;
+
+/// Reacts to an entity being despawned, as registered by `World::on_despawn`
+type DespawnHook = dyn FnMut(Entity, &World);
+
+/// The kinds of system a world can drive
+enum System {
+    Iterative(Box<dyn IterativeSystem>),
+    Pairwise(Box<dyn PairwiseSystem>),
+    Parallel(Box<dyn ParallelSystem>),
+    /// Left in place of a system removed by `unregister_system`, so the
+    /// handles of the systems registered after it stay valid
+    Unregistered
+}
+
+impl SystemEntry {
+    fn new<T>(system: T) -> SystemEntry where T: IterativeSystem + 'static {
+        SystemEntry::from_parts::<T>(System::Iterative(Box::new(system)), T::get_query(), T::reads(), T::writes())
+    }
+
+    fn pairwise<T>(system: T) -> SystemEntry where T: PairwiseSystem + 'static {
+        SystemEntry::from_parts::<T>(System::Pairwise(Box::new(system)), T::get_query(), T::reads(), T::writes())
+    }
+
+    fn parallel<T>(system: T) -> SystemEntry where T: ParallelSystem + 'static {
+        SystemEntry::from_parts::<T>(System::Parallel(Box::new(system)), T::get_query(), T::reads(), T::writes())
+    }
+
+    /// The system's name, as reported by its `name` method
+    fn name(&self) -> &'static str {
+        match *self.system.borrow() {
+            System::Iterative(ref system) => system.name(),
+            System::Pairwise(ref system) => system.name(),
+            System::Parallel(ref system) => system.name(),
+            System::Unregistered => "unregistered"
+        }
+    }
+
+    /// Whether the system splits its entities across the thread pool under
+    /// `process_parallel`
+    #[cfg(feature = "parallel")]
+    fn is_parallel(&self) -> bool {
+        matches!(*self.system.borrow(), System::Parallel(_))
+    }
+
+    /// Whether the system hasn't been unregistered. A system that's running is
+    /// still registered.
+    fn is_registered(&self) -> bool {
+        self.system.try_borrow().map_or(true, |system| !matches!(*system, System::Unregistered))
+    }
+
+    #[cfg_attr(not(feature = "parallel"), allow(unused_variables))]
+    fn from_parts<T: 'static>(system: System, query: Query, reads: Vec<TypeId>, writes: Option<Vec<TypeId>>) -> SystemEntry {
+        SystemEntry {
+            system: RefCell::new(system),
+            ty: TypeId::of::<T>(),
+            #[cfg(feature = "tracing")]
+            type_name: any::type_name::<T>(),
+            run_criteria: None,
+            stage: 0,
+            enabled: true,
+            query,
+            #[cfg(feature = "parallel")]
+            access: Access {
+                reads,
+                writes
+            },
+            matches: RefCell::new(MatchCache {
+                checked: 0,
+                archetypes: Vec::new(),
+                version: None,
+                entities: Vec::new(),
+                candidates: Vec::new(),
+                selected: Vec::new()
+            })
+        }
+    }
+}
+
+/// Yields the entities of two ascending lists in ascending order
+struct Merged<'a>(&'a [Entity], &'a [Entity]);
+
+impl<'a> Iterator for Merged<'a> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let list = match (self.0.first(), self.1.first()) {
+            (Some(a), Some(b)) if a <= b => &mut self.0,
+            (Some(_), None) => &mut self.0,
+            (_, Some(_)) => &mut self.1,
+            (None, None) => return None
+        };
+
+        let (&ent, rest) = list.split_first().unwrap();
+        *list = rest;
+        Some(ent)
+    }
+}
+
+/// Leaves a batch started by `World::apply` when dropped, so that a panic in
+/// the batch doesn't leave every later change batched too
+struct Batch<'a>(&'a mut World);
+
+impl<'a> Drop for Batch<'a> {
+    fn drop(&mut self) {
+        self.0.batch_depth -= 1;
+    }
+}
+
+/// Pointer handed to the tasks `process_parallel` runs on the thread pool
+#[cfg(feature = "parallel")]
+struct Shared<T>(*const T);
+
+#[cfg(feature = "parallel")]
+unsafe impl<T> Send for Shared<T> {}
+
+#[cfg(feature = "parallel")]
+unsafe impl<T> Sync for Shared<T> {}
+
+/// Panics in debug builds if a system run by `process_parallel` reads a `T`
+/// without declaring it
+#[inline]
+fn check_read<T: Any>() {
+    #[cfg(all(feature = "parallel", debug_assertions))]
+    schedule::check_read(TypeId::of::<T>(), any::type_name::<T>());
+}
+
+/// Panics in debug builds if a system run by `process_parallel` writes a `T`
+/// without declaring it
+#[inline]
+fn check_write<T: Any>() {
+    #[cfg(all(feature = "parallel", debug_assertions))]
+    schedule::check_write(TypeId::of::<T>(), any::type_name::<T>());
+}
+
+/// Panics in debug builds if a system run by `process_parallel` adds or removes
+/// entities or components
+#[inline]
+fn check_structural() {
+    #[cfg(all(feature = "parallel", debug_assertions))]
+    schedule::check_structural();
+}
+
+struct MatchCache {
+    /// Number of archetypes already tested against the query
+    checked: usize,
+    /// Matching archetypes, and whether every entity in them matches or
+    /// each must be tested individually
+    archetypes: Vec<(usize, bool)>,
+    /// The world's `structure_version` when `entities` was last rebuilt
+    version: Option<u64>,
+    entities: Vec<Entity>,
+    /// Entities of archetypes that might match, depending on the values of
+    /// their components
+    candidates: Vec<Entity>,
+    /// The candidates that matched when they were last tested
+    selected: Vec<Entity>
 }
 
 impl World {
     /// Create a new ECS world with a default capacity for entities of 131072
     pub fn new() -> World {
-        World::with_capacity(131072)
+        World::builder().build()
     }
 
     /// Create a new world with custom initial capacity specified
     pub fn with_capacity(capacity: usize) -> World {
-        World {
-            entities: Vec::with_capacity(capacity),
-            iterative_systems: Vec::new(),
-            free_ents: VecDeque::with_capacity(capacity / 3),
-            dead_ents: RefCell::new(VecDeque::with_capacity(capacity / 3)),
-            valid_ents: vec![false; capacity]
-        }
+        World::builder().initial_capacity(capacity).build()
     }
 
-    /// Registers a new iterative system, which will be called for every entity that
-    /// matches its query on every tick.
+    /// Starts configuring a new world, for control over how its storage is
+    /// allocated
+    pub fn builder() -> WorldBuilder {
+        WorldBuilder::default()
+    }
+
+    /// Registers a new iterative system in the default stage, which will be called
+    /// for every entity that matches its query on every tick.
     /// 
     /// # Examples
     /// ```
@@ -73,167 +797,4082 @@ impl World {
     /// let ent = world.create_entity();
     /// world.add_component(ent, Phys { mass: 100.0 });
     /// ```
-    pub fn register_iterative_system<T>(&mut self, system: T) where T: IterativeSystem + 'static {
-        self.iterative_systems.push((RefCell::new(Box::new(system)), T::get_query()));
-    } 
-
-    /// Allocates space for a new entity and returns its ID
-    pub fn create_entity(&mut self) -> Entity {
-        if self.free_ents.len() > 0 {
-            let ent = self.free_ents.pop_front().unwrap();
-            let e = self.entities.get_mut(ent).unwrap();
-            e.borrow_mut().truncate(0);
-            self.valid_ents[ent] = false;
+    /// 
+    /// The system's `on_register` is called before this returns.
+    pub fn register_iterative_system<T>(&mut self, mut system: T) -> SystemHandle where T: IterativeSystem + 'static {
+        system.on_register(self);
+        self.push_system(SystemEntry::new(system))
+    }
 
-            ent
-        } else {
-            let ent = self.entities.len();
-            self.entities.push(RefCell::new(Vec::with_capacity(12)));
-            self.valid_ents[ent] = true;
+    /// Registers an iterative system that only runs on ticks where `criteria`
+    /// returns true. `criteria` is called with the world before the system
+    /// would run, so it can gate the system on resources or other world state.
+    pub fn register_iterative_system_if<T, F>(&mut self, system: T, criteria: F) -> SystemHandle
+        where T: IterativeSystem + 'static, F: Fn(&World) -> bool + 'static {
+        let mut system = system;
+        system.on_register(self);
 
-            ent
-        }
+        let mut entry = SystemEntry::new(system);
+        entry.run_criteria = Some(Box::new(criteria));
+        self.push_system(entry)
     }
 
-    /// Removes an entity from the world and cleans up its components
-    pub fn drop_entity(&mut self, ent: Entity) {
-        if ent < self.entities.len() {
-            let e = self.entities.get_mut(ent).unwrap();
-            for comp in e.borrow().iter() {
-                unsafe {
-                    // Drop component memory
-                    Box::from_raw(comp.1);
-                }
-            }
-
-            self.valid_ents[ent] = false;
+    /// Registers a pairwise system, which will be called for every unordered pair
+    /// of distinct entities matching its query on every tick. Pairwise systems run
+    /// in registration order alongside iterative systems.
+    pub fn register_pairwise_system<T>(&mut self, system: T) -> SystemHandle where T: PairwiseSystem + 'static {
+        self.push_system(SystemEntry::pairwise(system))
+    }
 
-            self.free_ents.push_back(ent);
-        }
+    /// Registers a parallel system, which will be called for every entity that
+    /// matches its query on every tick, like an iterative system. Under
+    /// `process_parallel` its entities are split across the thread pool.
+    pub fn register_parallel_system<T>(&mut self, system: T) -> SystemHandle where T: ParallelSystem + 'static {
+        self.push_system(SystemEntry::parallel(system))
     }
 
-    /// Schedules an entity to be removed from the world on the next tick
-    pub fn remove_entity(&self, ent: Entity) {
-        if ent < self.entities.len() {
-            self.dead_ents.borrow_mut().push_back(ent);
+    /// Adds a stage named `name` that runs after every existing stage. Each tick
+    /// the systems of each stage run in turn, in the order they were registered,
+    /// before any system of the next stage. Adding a stage that already exists
+    /// does nothing.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// let mut world = World::new();
+    /// world.add_stage("physics");
+    /// world.add_stage("render");
+    /// ```
+    pub fn add_stage(&mut self, name: &str) {
+        if !self.stages.iter().any(|stage| stage == name) {
+            self.stages.push(String::from(name));
         }
     }
 
-    /// Add a component of type `T` to entity `ent` and returns whether or not
-    /// the operation was successful.
-    pub fn add_component<T: Any>(&self, ent: Entity, component: T) -> bool {
-        match self.valid_ents.get(ent) {
-            Some(&true) => {
-                let ty = TypeId::of::<T>();
-                let mut components = self.entities[ent].borrow_mut();
+    /// Registers an iterative system like `register_iterative_system`, but in the
+    /// stage named `stage` rather than the default stage.
+    /// 
+    /// # Panics
+    /// Panics if no stage named `stage` has been added.
+    pub fn register_iterative_system_in<T>(&mut self, stage: &str, mut system: T) -> SystemHandle where T: IterativeSystem + 'static {
+        let stage = match self.stages.iter().position(|name| name == stage) {
+            Some(stage) => stage,
+            None => panic!("no stage named {:?}", stage)
+        };
 
-                components.push((ty, Box::into_raw(Box::new(component))));
-                
-                true
-            },
-            _ => false
-        }
+        system.on_register(self);
+
+        let mut entry = SystemEntry::new(system);
+        entry.stage = stage;
+        self.push_system(entry)
     }
 
-    /// Get the component of type `T` from entity `ent`
-    pub fn get_component<T: Any>(&self, ent: Entity) -> Option<&mut T> {
-        match self.valid_ents.get(ent) {
-            Some(&true) => {
-                let ty = TypeId::of::<T>();
-                let components = &self.entities[ent];
-                for &(comp_ty, ptr) in components.borrow().iter() {
-                    if comp_ty == ty {
-                        unsafe {
-                            return Some(&mut *(ptr as *mut T));
-                        }
-                    }
-                }
+    fn push_system(&mut self, entry: SystemEntry) -> SystemHandle {
+        let stage = entry.stage;
+        self.systems.push(entry);
 
-                None
-            },
-            _ => None
-        }
+        let i = self.systems.len() - 1;
+        let pos = self.order.iter().position(|&j| self.systems[j].stage > stage).unwrap_or(self.order.len());
+        self.order.insert(pos, i);
 
+        self.system_handle(i)
     }
 
-    /// Check whether entity `ent` has a component of type `T`
-    pub fn has_component<T: Any>(&self, ent: Entity) -> bool {
-        match self.valid_ents.get(ent) {
-            Some(&true) => {
-                let ty = TypeId::of::<T>();
-                let components = &self.entities[ent];
-                for &(comp_ty, _) in components.borrow().iter() {
-                    if comp_ty == ty {
-                        return true;
-                    }
-                }
+    fn system_handle(&self, index: usize) -> SystemHandle {
+        SystemHandle { world: self.id, index }
+    }
 
-                false
+    /// The index into `systems` of the system identified by `handle`, or `None`
+    /// if `handle` came from another world
+    fn system_index(&self, handle: SystemHandle) -> Option<usize> {
+        if handle.world == self.id {
+            Some(handle.index)
+        } else {
+            None
+        }
+    }
 
-            },
-            _ => false
+    /// The name of the system identified by `handle`, or `None` if it has been
+    /// unregistered or `handle` came from another world
+    pub fn system_name(&self, handle: SystemHandle) -> Option<&'static str> {
+        let sys = self.systems.get(self.system_index(handle)?)?;
+        if sys.is_registered() {
+            Some(sys.name())
+        } else {
+            None
         }
     }
 
-    /// The main loop for a world. Calling `process` runs all ready systems in this world.
-    pub fn process(&mut self) {
-        for (ent, e) in self.entities.iter().enumerate() {
-            if self.valid_ents[ent] {
-                for sys in self.iterative_systems.iter() {
-                    if sys.1.test(&e) {
-                        sys.0.borrow_mut().process(ent, self);
-                    }
-                }
-            }
+    /// Suspends or resumes the system identified by `handle`. Disabled systems
+    /// are skipped by `process` but keep their state, and run again once
+    /// re-enabled. Returns false if the system has been unregistered or `handle`
+    /// came from another world.
+    pub fn set_system_enabled(&mut self, handle: SystemHandle, enabled: bool) -> bool {
+        let sys = match self.system_index(handle) {
+            Some(i) => &mut self.systems[i],
+            None => return false
+        };
+        if let System::Unregistered = *sys.system.get_mut() {
+            return false;
         }
 
-        if self.dead_ents.borrow().len() > 0 {
-            loop  {
-                let dead_ent = self.dead_ents.borrow_mut().pop_front();
-                if dead_ent.is_some() {
-                    self.drop_entity(dead_ent.unwrap());
-                } else {
-                    return;
-                }
-            }
+        sys.enabled = enabled;
+        true
+    }
+
+    /// Removes the system identified by `handle` from the world, calling its
+    /// `on_unregister` if it's an iterative system. Returns false if the system
+    /// was already unregistered or `handle` came from another world. The
+    /// handles of other systems stay valid.
+    pub fn unregister_system(&mut self, handle: SystemHandle) -> bool {
+        let sys = match self.system_index(handle) {
+            Some(i) => &mut self.systems[i],
+            None => return false
+        };
+        sys.enabled = false;
+        sys.run_criteria = None;
+        // Accessing nothing, the entry no longer splits up parallel batches
+        #[cfg(feature = "parallel")]
+        {
+            sys.access = Access { reads: Vec::new(), writes: Some(Vec::new()) };
+        }
+
+        match mem::replace(sys.system.get_mut(), System::Unregistered) {
+            System::Iterative(mut system) => system.on_unregister(self),
+            System::Pairwise(_) | System::Parallel(_) => (),
+            System::Unregistered => return false
         }
+
+        true
     }
-}
 
-#[cfg(test)]
+    /// Gets the first registered iterative system of type `S` mutably, so it
+    /// can be reconfigured between ticks
+    pub fn get_system_mut<S>(&mut self) -> Option<&mut S> where S: IterativeSystem + 'static {
+        let ty = TypeId::of::<S>();
+        self.systems.iter_mut()
+            .filter(|entry| entry.ty == ty)
+            .find_map(|entry| match *entry.system.get_mut() {
+                // The entry's type was recorded from the system it boxed
+                System::Iterative(ref mut system) => Some(unsafe { &mut *(&mut **system as *mut dyn IterativeSystem as *mut S) }),
+                System::Pairwise(_) | System::Parallel(_) | System::Unregistered => None
+            })
+    }
+
+    /// Counts the iterative and pairwise systems registered with the world,
+    /// not including pending startup systems or those since unregistered
+    pub fn system_count(&self) -> usize {
+        self.systems.iter().filter(|entry| entry.is_registered()).count()
+    }
+
+    /// Check whether a system of type `S` is registered with the world, such
+    /// as to avoid registering it twice
+    pub fn is_system_registered<S: 'static>(&self) -> bool {
+        let ty = TypeId::of::<S>();
+        self.systems.iter().any(|entry| entry.ty == ty && entry.is_registered())
+    }
+
+    /// Registers a startup system, which is called for every entity matching its
+    /// query a single time and then discarded. Startup systems run at the start
+    /// of the next call to `process`, or earlier through `run_startup`.
+    pub fn register_startup_system<T>(&mut self, system: T) where T: IterativeSystem + 'static {
+        self.startup_systems.push(SystemEntry::new(system));
+    }
+
+    /// Runs and discards every pending startup system, in the order they were
+    /// registered.
+    pub fn run_startup(&mut self) {
+        let systems = mem::take(&mut self.startup_systems);
+        for sys in systems.iter() {
+            self.run_system(sys);
+        }
+    }
+
+    /// Allocates space for a new entity and returns its ID, reusing the ID of a
+    /// removed entity according to the world's `ReusePolicy` if there is one
+    pub fn create_entity(&mut self) -> Entity {
+        let free = match self.reuse {
+            ReusePolicy::Fifo => self.free_ents.pop_front(),
+            ReusePolicy::Lifo => self.free_ents.pop_back()
+        };
+        let ent = if let Some(ent) = free {
+            let e = self.entities.get_mut(ent).unwrap();
+            e.borrow_mut().clear();
+            self.valid_ents[ent].set(true);
+
+            ent
+        } else {
+            let ent = self.entities.len();
+            if ent == self.capacity() {
+                self.grow();
+            }
+            self.entities.push(RefCell::new(Components::with_capacity(12)));
+            self.valid_ents.push(Cell::new(true));
+
+            ent
+        };
+
+        self.archetypes.borrow_mut().relocate(ent, &self.entities[ent].borrow());
+        self.structure_changed();
+
+        ent
+    }
+
+    /// The number of entities the world can hold before its storage has to grow
+    pub fn capacity(&self) -> usize {
+        self.entities.capacity().min(self.valid_ents.capacity())
+    }
+
+    /// Reserves room for at least `additional` more entities than the world has
+    /// ever held, avoiding repeated reallocation while spawning many at once.
+    pub fn reserve(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+        self.valid_ents.reserve(additional);
+    }
+
+    /// Runs `f` as a batch of structural changes, such as spawning entities and
+    /// adding or removing components. However many changes `f` makes, each
+    /// system's cached matches are invalidated once, and are rebuilt once
+    /// before this returns rather than the next time each system runs.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// struct Tree;
+    /// 
+    /// let mut world = World::new();
+    /// world.apply(|world| {
+    ///     for _ in 0..1000 {
+    ///         world.spawn().add(Tree);
+    ///     }
+    /// });
+    /// assert_eq!(world.count_components::<Tree>(), 1000);
+    /// ```
+    pub fn apply<F: FnOnce(&mut World)>(&mut self, f: F) {
+        self.batch_depth += 1;
+        {
+            let batch = Batch(self);
+            f(&mut *batch.0);
+        }
+
+        if self.batch_depth == 0 && self.batched_change.get() {
+            for sys in self.systems.iter() {
+                self.refresh_matches(sys);
+            }
+        }
+    }
+
+    /// Makes room for more entities according to the world's growth policy
+    fn grow(&mut self) {
+        let additional = match self.growth {
+            GrowthPolicy::Double => self.capacity().max(1),
+            GrowthPolicy::Fixed(step) => step.max(1),
+            GrowthPolicy::Exact => 1
+        };
+
+        self.entities.reserve_exact(additional);
+        self.valid_ents.reserve_exact(additional);
+    }
+
+    /// Releases as much unused entity storage as possible, such as after a
+    /// level has been unloaded. Removed entities' IDs are still reused rather
+    /// than given back, so the storage can't shrink below the highest ID ever
+    /// handed out.
+    pub fn shrink_to_fit(&mut self) {
+        self.entities.shrink_to_fit();
+        self.valid_ents.shrink_to_fit();
+        self.free_ents.shrink_to_fit();
+        self.dead_ents.get_mut().shrink_to_fit();
+    }
+
+    /// Moves every live entity to the lowest free IDs, in the same order, so
+    /// that IDs are contiguous from `0`, then releases the storage left unused
+    /// as `shrink_to_fit` does. Entities scheduled for removal are dropped
+    /// first. Meant to be run between levels after heavy churn, not every tick.
+    /// 
+    /// Returns the new ID of every live entity, keyed by its old one, so that
+    /// IDs stored elsewhere can be updated. `Parent` and `Children` components
+    /// are updated already, as is the world's `SpatialGrid` if it has one.
+    /// 
+    /// # Panics
+    /// Panics if any component is currently borrowed.
+    pub fn defragment(&mut self) -> HashMap<Entity, Entity> {
+        self.flush_dead_entities();
+
+        let live: Vec<Entity> = self.iter_entities().collect();
+        let mut moved = HashMap::new();
+        for (new, &old) in live.iter().enumerate() {
+            // Entities are moved in ascending order, so the new ID is always
+            // free by the time its entity gets to it
+            self.entities.swap(new, old);
+            self.valid_ents.swap(new, old);
+            moved.insert(old, new);
+        }
+
+        self.entities.truncate(live.len());
+        self.valid_ents.truncate(live.len());
+        self.free_ents.clear();
+
+        let archetypes = self.archetypes.get_mut();
+        archetypes.clear_entities();
+        for (ent, components) in self.entities.iter_mut().enumerate() {
+            archetypes.relocate(ent, components.get_mut());
+        }
+
+        let evented = mem::take(self.evented_ents.get_mut());
+        *self.evented_ents.get_mut() = evented.into_iter().filter_map(|ent| moved.get(&ent).cloned()).collect();
+        let changed = mem::take(self.changed_ents.get_mut());
+        *self.changed_ents.get_mut() = changed.into_iter().filter_map(|ent| moved.get(&ent).cloned()).collect();
+
+        self.remap_hierarchy(&moved);
+        if let Some(grid) = self.get_resource::<SpatialGrid>() {
+            grid.remap(&moved);
+        }
+        self.structure_changed();
+        self.shrink_to_fit();
+
+        moved
+    }
+
+    /// Creates a new entity holding each of the components in `bundle` and
+    /// returns its ID
+    pub fn spawn_bundle<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let ent = self.create_entity();
+        bundle.insert_into(self, ent);
+
+        ent
+    }
+
+    /// Moves every live entity in `other`, along with its components, into this
+    /// world under a newly allocated ID. Returns a map from each entity's ID in
+    /// `other` to its new ID.
+    /// 
+    /// Only entities are merged: systems and resources registered on `other` are
+    /// dropped along with it. `Parent` and `Children` components are updated to
+    /// the merged entities' new IDs.
+    pub fn merge(&mut self, mut other: World) -> HashMap<Entity, Entity> {
+        other.flush_dead_entities();
+
+        let mut ids = HashMap::new();
+        for ent in 0..other.entities.len() {
+            if !other.valid_ents[ent].get() {
+                continue;
+            }
+
+            let new_ent = self.create_entity();
+            let mut components = mem::replace(other.entities[ent].get_mut(), Components::with_capacity(0));
+            self.adopt_components(&mut components, other.columns.get_mut());
+            self.archetypes.get_mut().relocate(new_ent, &components);
+            if components.has_events() {
+                self.evented_ents.get_mut().push(new_ent);
+            }
+            // Listed whether or not `other` had it listed, as this world hasn't
+            components.mark_listed();
+            self.changed_ents.get_mut().push(new_ent);
+            *self.entities[new_ent].get_mut() = components;
+
+            ids.insert(ent, new_ent);
+        }
+        self.structure_changed();
+        self.remap_hierarchy(&ids);
+        self.component_sizes.extend(mem::take(&mut other.component_sizes));
+        #[cfg(feature = "debug-names")]
+        self.type_names.get_mut().extend(mem::take(other.type_names.get_mut()));
+
+        ids
+    }
+
+    /// Moves entity `ent`, along with all of its components, into `dst` and
+    /// returns its ID there, or `None` if `ent` is invalid. The components
+    /// themselves aren't copied, and `ent` is gone from this world straight
+    /// away, without running any `on_despawn` hooks.
+    /// 
+    /// The entity leaves this world's hierarchy: it's taken out of its parent's
+    /// `Children`, its children lose their `Parent`, and it arrives in `dst`
+    /// without `Parent` or `Children` of its own. It's also taken out of this
+    /// world's `SpatialGrid`.
+    pub fn move_entity_to(&mut self, ent: Entity, dst: &mut World) -> Option<Entity> {
+        if !self.is_valid(ent) {
+            return None;
+        }
+
+        self.unlink(ent);
+        self.remove_component::<Parent>(ent);
+        self.remove_component::<Children>(ent);
+        if let Some(grid) = self.get_resource::<SpatialGrid>() {
+            grid.remove(ent);
+        }
+
+        let mut components = mem::replace(self.entities[ent].get_mut(), Components::with_capacity(0));
+        self.release_entity(ent);
+
+        for &(ty, _) in components.iter() {
+            if let Some(&size) = self.component_sizes.get(&ty) {
+                dst.component_sizes.insert(ty, size);
+            }
+            #[cfg(feature = "debug-names")]
+            {
+                if let Some(&name) = self.type_names.get_mut().get(&ty) {
+                    dst.type_names.get_mut().insert(ty, name);
+                }
+            }
+        }
+
+        let new_ent = dst.create_entity();
+        dst.adopt_components(&mut components, self.columns.get_mut());
+        dst.archetypes.get_mut().relocate(new_ent, &components);
+        if components.has_events() {
+            dst.evented_ents.get_mut().push(new_ent);
+        }
+        components.mark_listed();
+        dst.changed_ents.get_mut().push(new_ent);
+        *dst.entities[new_ent].get_mut() = components;
+        dst.structure_changed();
+
+        Some(new_ent)
+    }
+
+    /// Moves any of `components` stored in the dense columns of another world
+    /// out of them, and any of a type registered as dense in this world into
+    /// this world's columns.
+    fn adopt_components(&mut self, components: &mut Components, from: &mut HashMap<TypeId, Box<dyn Column>>) {
+        let columns = self.columns.get_mut();
+        if columns.is_empty() && from.is_empty() {
+            return;
+        }
+
+        let moved: Vec<Component> = components.iter().cloned().collect();
+        for (ty, ptr) in moved {
+            let value = match from.get_mut(&ty) {
+                Some(column) => unsafe { column.remove_boxed(ptr) },
+                None if columns.contains_key(&ty) => unsafe { Box::from_raw(ptr) },
+                None => continue
+            };
+
+            let ptr = match columns.get_mut(&ty) {
+                Some(column) => column.insert_boxed(value),
+                None => Box::into_raw(value)
+            };
+            components.insert((ty, ptr));
+        }
+    }
+
+    /// Stores all components of type `T` next to each other in fixed-size
+    /// blocks, rather than boxing each one separately, so that systems visiting
+    /// many entities with a `T` read memory in order. Components of type `T`
+    /// already in the world are moved into the new storage; registering a type
+    /// twice does nothing.
+    /// 
+    /// Dense components are accessed exactly like boxed ones, through
+    /// `get_component` and queries, and are never moved once added.
+    pub fn register_dense_component<T: component::Component>(&mut self) {
+        let ty = TypeId::of::<T>();
+        if self.columns.get_mut().contains_key(&ty) {
+            return;
+        }
+        self.record_size::<T>();
+
+        // Entities waiting to be dropped still hold boxed components
+        self.flush_dead_entities();
+
+        let mut column = DenseColumn::<T>::new();
+        for ent in self.iter_entities().collect::<Vec<_>>() {
+            let components = self.entities[ent].get_mut();
+            if let Some(ptr) = components.get(ty) {
+                let value = unsafe { Box::from_raw(ptr as *mut T) };
+                components.insert((ty, column.insert(*value)));
+                if components.mark_listed() {
+                    self.changed_ents.get_mut().push(ent);
+                }
+            }
+        }
+
+        self.columns.get_mut().insert(ty, Box::new(column));
+    }
+
+    /// Puts a new component where components of its type are kept
+    fn store<T: component::Component>(&self, component: T) -> *mut dyn Any {
+        let mut columns = self.columns.borrow_mut();
+        if !columns.is_empty() {
+            if let Some(column) = columns.get_mut(&TypeId::of::<T>()) {
+                return column.as_any_mut().downcast_mut::<DenseColumn<T>>().unwrap().insert(component);
+            }
+        }
+
+        Box::into_raw(Box::new(component))
+    }
+
+    /// Takes a component out of wherever components of its type are kept,
+    /// boxing it if it was stored densely.
+    /// 
+    /// # Safety
+    /// `component` must no longer be held by any entity.
+    unsafe fn take_component(&self, component: Component) -> Box<dyn Any> {
+        let (ty, ptr) = component;
+        let mut columns = self.columns.borrow_mut();
+        if !columns.is_empty() {
+            if let Some(column) = columns.get_mut(&ty) {
+                return column.remove_boxed(ptr);
+            }
+        }
+
+        Box::from_raw(ptr)
+    }
+
+    /// Removes an entity from the world and cleans up its components, leaving
+    /// its storage empty until the ID is reused. Entities already dropped are
+    /// left alone, and an entity scheduled for removal by `remove_entity` is
+    /// dropped straight away instead.
+    pub fn drop_entity(&mut self, ent: Entity) {
+        if ent >= self.entities.len() {
+            return;
+        }
+
+        if !self.valid_ents[ent].get() {
+            let dead_ents = self.dead_ents.get_mut();
+            match dead_ents.iter().position(|&dead| dead == ent) {
+                Some(i) => {
+                    dead_ents.remove(i);
+                },
+                None => return
+            }
+        }
+
+        self.free_entity(ent);
+    }
+
+    /// Frees the components of an entity that's live or scheduled for removal,
+    /// and makes its ID available again
+    fn free_entity(&mut self, ent: Entity) {
+        if !self.despawn_hooks.is_empty() {
+            self.run_despawn_hooks(ent);
+        }
+
+        let linked = self.component_ptr(ent, TypeId::of::<Parent>()).is_some()
+            || self.component_ptr(ent, TypeId::of::<Children>()).is_some();
+        if linked {
+            // Entities scheduled for removal are already invalid, but still
+            // hold the components needed to unlink them
+            let was_valid = self.valid_ents[ent].replace(true);
+            self.unlink(ent);
+            self.valid_ents[ent].set(was_valid);
+        }
+
+        for &comp in self.entities[ent].borrow().iter() {
+            unsafe {
+                // Drop component memory
+                drop(self.take_component(comp));
+            }
+        }
+        self.entities[ent].get_mut().clear();
+
+        self.release_entity(ent);
+    }
+
+    /// Makes the ID of an entity whose components have been freed or moved
+    /// away available again
+    fn release_entity(&mut self, ent: Entity) {
+        self.valid_ents[ent].set(false);
+        self.archetypes.borrow_mut().remove(ent);
+        self.structure_changed();
+
+        self.free_ents.push_back(ent);
+    }
+
+    /// Registers `hook` to be called with every entity just before its
+    /// components are freed, whether it's dropped straight away or at the end of
+    /// a tick after `remove_entity`. While the hook runs the entity is treated
+    /// as live again, so its components can be read one last time.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// 
+    /// struct GpuHandle(u32);
+    /// 
+    /// let released = Rc::new(RefCell::new(Vec::new()));
+    /// let mut world = World::new();
+    /// let log = released.clone();
+    /// world.on_despawn(move |ent, world| {
+    ///     if let Some(handle) = world.get_component_ref::<GpuHandle>(ent) {
+    ///         log.borrow_mut().push(handle.0);
+    ///     }
+    /// });
+    /// 
+    /// let ent = world.create_entity();
+    /// world.add_component(ent, GpuHandle(7));
+    /// world.drop_entity(ent);
+    /// assert_eq!(*released.borrow(), vec!(7));
+    /// ```
+    pub fn on_despawn<F: FnMut(Entity, &World) + 'static>(&mut self, hook: F) {
+        self.despawn_hooks.push(Box::new(hook));
+    }
+
+    fn run_despawn_hooks(&mut self, ent: Entity) {
+        let mut hooks = mem::take(&mut self.despawn_hooks);
+        let was_valid = self.valid_ents[ent].replace(true);
+        for hook in hooks.iter_mut() {
+            hook(ent, self);
+        }
+        self.valid_ents[ent].set(was_valid);
+        self.despawn_hooks = hooks;
+
+        // A hook removing the entity again mustn't have it dropped twice
+        self.dead_ents.get_mut().retain(|&dead| dead != ent);
+    }
+
+    /// Drops every entity in the world, freeing their components, and resets the
+    /// pool of entity IDs so the next entity created is `0` again. Systems and
+    /// resources are kept, as is the capacity allocated for entities.
+    pub fn clear(&mut self) {
+        self.flush_dead_entities();
+        if !self.despawn_hooks.is_empty() {
+            for ent in self.iter_entities().collect::<Vec<_>>() {
+                self.run_despawn_hooks(ent);
+            }
+        }
+
+        for ent in 0..self.entities.len() {
+            if self.valid_ents[ent].get() {
+                for &comp in self.entities[ent].borrow().iter() {
+                    unsafe {
+                        drop(self.take_component(comp));
+                    }
+                }
+            }
+        }
+
+        self.entities.clear();
+        self.valid_ents.clear();
+        self.free_ents.clear();
+        self.evented_ents.get_mut().clear();
+        self.changed_ents.get_mut().clear();
+        self.archetypes.get_mut().clear_entities();
+        self.structure_changed();
+    }
+
+    /// Schedules an entity to be removed from the world at the end of the current
+    /// tick. The entity is treated as gone straight away: its components can no
+    /// longer be looked up and no system will visit it again, but their memory
+    /// isn't freed until nothing can still be holding on to it.
+    pub fn remove_entity(&self, ent: Entity) {
+        if let Some(valid) = self.valid_ents.get(ent) {
+            if valid.get() {
+                check_structural();
+                valid.set(false);
+                self.dead_ents.borrow_mut().push_back(ent);
+            }
+        }
+    }
+
+    /// Immediately removes every entity matching `matchers` from the world, freeing
+    /// their components, and returns how many were removed.
+    /// 
+    /// Unlike `remove_entity`, which defers removal to the end of the next tick,
+    /// the entities are gone as soon as this returns.
+    pub fn despawn_matching(&mut self, matchers: QueryBuilder) -> usize {
+        let query = matchers.build();
+        let mut ents = Vec::new();
+        self.matching_entities(&query, &mut ents);
+
+        for &ent in ents.iter() {
+            self.drop_entity(ent);
+        }
+
+        ents.len()
+    }
+
+    /// Immediately removes every live entity for which `pred` returns true,
+    /// freeing their components, and returns how many were removed. For
+    /// criteria a query can't express, such as ones depending on a resource.
+    ///
+    /// Every entity is tested before any is removed, so `pred` sees the world
+    /// as it was when this was called.
+    pub fn despawn_if<F>(&mut self, pred: F) -> usize where F: Fn(Entity, &World) -> bool {
+        let ents: Vec<Entity> = self.iter_entities().filter(|&ent| pred(ent, self)).collect();
+
+        for &ent in ents.iter() {
+            self.drop_entity(ent);
+        }
+
+        ents.len()
+    }
+
+    /// Returns the lowest numbered entity matching `matchers`, stopping at the
+    /// first match.
+    pub fn find_first(&self, matchers: QueryBuilder) -> Option<Entity> {
+        let query = matchers.build();
+        for (ent, e) in self.entities.iter().enumerate() {
+            if self.valid_ents[ent].get() && query.matches(e) {
+                return Some(ent);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the `n`th entity matching `matchers`, counting from `0` in
+    /// ascending order, or `None` if fewer than `n + 1` entities match. Stops
+    /// scanning at the `n`th match.
+    pub fn get_nth_matching(&self, matchers: QueryBuilder, n: usize) -> Option<Entity> {
+        self.filter_entities(matchers).nth(n)
+    }
+
+    /// Check whether any entity matches `matchers`
+    pub fn any(&self, matchers: QueryBuilder) -> bool {
+        self.find_first(matchers).is_some()
+    }
+
+    /// Counts the entities matching `matchers` without collecting them
+    pub fn count_matching(&self, matchers: QueryBuilder) -> usize {
+        let query = matchers.build();
+        let archetypes = self.archetypes.borrow();
+
+        let mut count = 0;
+        for id in 0..archetypes.len() {
+            let archetype = archetypes.get(id);
+            let exact = match query.test_signature(&archetype.signature) {
+                Some(false) => continue,
+                Some(true) => true,
+                None => false
+            };
+
+            count += archetype.entities.iter()
+                .filter(|&&ent| self.valid_ents[ent].get() && (exact || query.matches(&self.entities[ent])))
+                .count();
+        }
+
+        count
+    }
+
+    /// Counts the live entities holding a component of type `T`
+    pub fn count_components<T: component::Component>(&self) -> usize {
+        self.count_matching(Matchers::with::<T>())
+    }
+
+    /// Estimates the bytes taken up by the components of every live entity,
+    /// counting only the component types whose size the world knows: those
+    /// registered with `register_dense_component`, `register_named` or
+    /// `register_snapshot`. Memory owned by the components themselves, such as
+    /// the contents of a `Vec`, isn't included. Components of other types are
+    /// counted by `unmeasured_components`.
+    pub fn component_memory_usage(&self) -> usize {
+        self.measure_components().0
+    }
+
+    /// Counts the components of live entities whose types aren't included in
+    /// `component_memory_usage`
+    pub fn unmeasured_components(&self) -> usize {
+        self.measure_components().1
+    }
+
+    /// Creates a world configured like this one, with no systems or resources
+    /// other than `FrameContext`, in which every live entity of this world has
+    /// been created without components and every other ID is free to reuse in
+    /// the same order
+    pub(crate) fn empty_copy(&self) -> World {
+        let mut world = World::builder()
+            .initial_capacity(self.capacity())
+            .growth(self.growth)
+            .reuse(self.reuse)
+            .build();
+
+        for _ in 0..self.entities.len() {
+            world.create_entity();
+        }
+        for ent in 0..self.entities.len() {
+            if !self.valid_ents[ent].get() {
+                world.drop_entity(ent);
+            }
+        }
+
+        // Entities waiting to be dropped are already gone from the copy
+        world.free_ents = self.free_ents.clone();
+        world.free_ents.extend(self.dead_ents.borrow().iter().cloned());
+        world.stages = self.stages.clone();
+        world.component_sizes = self.component_sizes.clone();
+        #[cfg(feature = "debug-names")]
+        {
+            world.type_names = RefCell::new(self.type_names.borrow().clone());
+        }
+
+        world
+    }
+
+    /// The component types registered with `register_dense_component`
+    pub(crate) fn dense_types(&self) -> Vec<TypeId> {
+        self.columns.borrow().keys().cloned().collect()
+    }
+
+    /// Notes the size of component type `T` for `component_memory_usage`
+    pub(crate) fn record_size<T: component::Component>(&mut self) {
+        self.component_sizes.insert(TypeId::of::<T>(), mem::size_of::<T>());
+    }
+
+    /// Sums the sizes of the live components of known size, and counts the rest
+    fn measure_components(&self) -> (usize, usize) {
+        let archetypes = self.archetypes.borrow();
+
+        let (mut bytes, mut unknown) = (0, 0);
+        for id in 0..archetypes.len() {
+            let archetype = archetypes.get(id);
+            let live = archetype.entities.iter().filter(|&&ent| self.valid_ents[ent].get()).count();
+            for ty in archetype.signature.iter() {
+                match self.component_sizes.get(ty) {
+                    Some(size) => bytes += size * live,
+                    None => unknown += live
+                }
+            }
+        }
+
+        (bytes, unknown)
+    }
+
+    /// Lazily yields every live entity matching `matchers`, in ascending order.
+    /// Each entity is tested as the iterator reaches it, so entities changed
+    /// while iterating are matched against their current components.
+    pub fn filter_entities(&self, matchers: QueryBuilder) -> impl Iterator<Item = Entity> + '_ {
+        let query = matchers.build();
+        self.iter_entities().filter(move |&ent| query.matches(&self.entities[ent]))
+    }
+
+    /// Lazily yields every live entity matching an already built `query`, in
+    /// ascending order, like `filter_entities`. The query can be built once and
+    /// run again every tick.
+    pub fn filter_entities_with<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = Entity> + 'a {
+        self.iter_entities().filter(move |&ent| query.matches(&self.entities[ent]))
+    }
+
+    /// Lazily yields up to `limit` entities matching `matchers`, starting from the
+    /// `offset`th match in ascending order.
+    /// 
+    /// Matches aren't indexed, so reaching the first entity of a page still
+    /// tests every entity before it; paging deep into a large world costs about
+    /// as much as scanning it up to that point.
+    pub fn filter_entities_page(&self, matchers: QueryBuilder, offset: usize, limit: usize) -> impl Iterator<Item = Entity> + '_ {
+        self.filter_entities(matchers).skip(offset).take(limit)
+    }
+
+    /// Collects every entity matching `matchers` into a set, in ascending
+    /// order, testing archetypes as a whole where possible. Nothing is kept
+    /// between calls: each one gathers the matches afresh, and the set is a
+    /// snapshot that isn't updated as entities change, but knows its length up
+    /// front.
+    pub fn collect_matching(&self, matchers: QueryBuilder) -> MatchedEntities {
+        let mut entities = Vec::new();
+        self.entities_matching_into(matchers, &mut entities);
+
+        MatchedEntities::new(entities)
+    }
+
+    /// Replaces the contents of `out` with every entity matching `matchers`, in
+    /// ascending order, like `collect_matching`. Reusing the same buffer
+    /// every tick avoids allocating a new one each time.
+    pub fn entities_matching_into(&self, matchers: QueryBuilder, out: &mut Vec<Entity>) {
+        out.clear();
+        self.matching_entities(&matchers.build(), out);
+        out.sort_unstable();
+    }
+
+    /// Iterates over every live entity in the world, in ascending order
+    pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.valid_ents.iter().enumerate().filter(|&(_, valid)| valid.get()).map(|(ent, _)| ent)
+    }
+
+    /// Calls `f` with every live entity holding a `T` and a mutable reference to
+    /// that component, for passes over a single component type that don't need
+    /// a system, in ascending order. Entities are found by archetype, so the
+    /// rest aren't visited.
+    /// 
+    /// # Panics
+    /// Panics if any of the components is already borrowed.
+    pub fn for_each_component<T, F>(&self, mut f: F)
+        where T: component::Component, F: FnMut(Entity, &mut T) {
+        for (ent, mut component) in self.iter_components::<T>() {
+            f(ent, &mut component);
+        }
+    }
+
+    /// Lazily yields every live entity holding a `T`, in ascending order, along
+    /// with a mutable borrow of that component. The entities are gathered up
+    /// front, but each component is only borrowed once it's reached, and stays
+    /// borrowed for as long as its guard is held; entities whose `T` was removed
+    /// in the meantime are skipped.
+    /// 
+    /// # Panics
+    /// Panics when reaching a component that's already borrowed, such as one
+    /// held from an earlier call to `get_component`.
+    /// 
+    /// # Examples
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// struct Cooldown(u32);
+    /// 
+    /// let mut world = World::new();
+    /// world.spawn().add(Cooldown(0));
+    /// world.spawn().add(Cooldown(3));
+    /// 
+    /// let ready = world.iter_components::<Cooldown>().filter(|(_, cooldown)| cooldown.0 == 0).count();
+    /// assert_eq!(ready, 1);
+    /// ```
+    pub fn iter_components<T: component::Component>(&self) -> impl Iterator<Item = (Entity, ComponentMut<'_, T>)> + '_ {
+        let mut ents = Vec::new();
+        self.matching_entities(&Matchers::with::<T>().build(), &mut ents);
+        ents.sort_unstable();
+
+        ents.into_iter().filter_map(move |ent| self.get_component::<T>(ent).map(|component| (ent, component)))
+    }
+
+    /// Add a component of type `T` to entity `ent` and returns whether or not
+    /// the operation was successful. If `ent` already has a `T` it is dropped
+    /// and replaced by `component`.
+    /// 
+    /// # Panics
+    /// Panics if `ent`'s existing `T` is currently borrowed.
+    pub fn add_component<T: component::Component>(&self, ent: Entity, component: T) -> bool {
+        if !self.is_valid(ent) {
+            return false;
+        }
+        check_structural();
+
+        #[cfg(feature = "debug-names")]
+        self.type_names.borrow_mut().entry(TypeId::of::<T>()).or_insert(any::type_name::<T>());
+
+        self.insert_component(ent, (TypeId::of::<T>(), self.store(component)));
+        true
+    }
+
+    /// Adds a component of type `T` to entity `ent`, as `add_component`, and
+    /// mutably borrows it straight away, or returns `None` if `ent` is invalid.
+    /// The component stays borrowed until the returned guard is dropped.
+    /// 
+    /// # Panics
+    /// Panics if `ent`'s existing `T` is currently borrowed.
+    pub fn add_component_mut<T: component::Component>(&self, ent: Entity, component: T) -> Option<ComponentMut<'_, T>> {
+        if !self.add_component(ent, component) {
+            return None;
+        }
+
+        ComponentMut::new(&self.entities[ent], ent, &self.changed_ents)
+    }
+
+    /// Adds a clone of `component` to each valid entity in `ents`, as
+    /// `add_component`, skipping invalid ones. Returns how many entities it was
+    /// added to.
+    /// 
+    /// # Panics
+    /// Panics if any of the entities' existing `T` is currently borrowed.
+    pub fn bulk_add_component<T: component::Component + Clone>(&self, ents: &[Entity], component: T) -> usize {
+        ents.iter().filter(|&&ent| self.add_component(ent, component.clone())).count()
+    }
+
+    /// Adds a component whose type is only known at runtime to entity `ent`,
+    /// like `add_component`. Returns whether or not the operation was
+    /// successful.
+    /// 
+    /// # Panics
+    /// Panics if `component` isn't of the type `ty`, or if `ent`'s existing
+    /// component of that type is currently borrowed.
+    pub fn add_component_dyn(&self, ent: Entity, ty: TypeId, component: Box<dyn Any>) -> bool {
+        assert!((*component).type_id() == ty, "component isn't of the given type");
+        if !self.is_valid(ent) {
+            return false;
+        }
+        check_structural();
+
+        let ptr = match self.columns.borrow_mut().get_mut(&ty) {
+            Some(column) => column.insert_boxed(component),
+            None => Box::into_raw(component)
+        };
+        self.insert_component(ent, (ty, ptr));
+        true
+    }
+
+    /// Gives valid entity `ent` a component already in storage, freeing the
+    /// one it replaces
+    fn insert_component(&self, ent: Entity, component: Component) {
+        let ty = component.0;
+        let mut components = self.entities[ent].borrow_mut();
+
+        let replaced = components.insert(component);
+        if components.mark_listed() {
+            self.changed_ents.push(ent);
+        }
+        if replaced.is_none() {
+            self.archetypes.borrow_mut().relocate(ent, &components);
+            self.structure_changed();
+            if components.record(ty, Event::Added) {
+                self.evented_ents.borrow_mut().push(ent);
+            }
+        }
+
+        // The old component's destructor runs once the entity is no
+        // longer borrowed, in case it looks up other components
+        drop(components);
+        if let Some(old) = replaced {
+            unsafe {
+                drop(self.take_component((ty, old)));
+            }
+        }
+    }
+
+    /// Removes the component of type `T` from each entity in `ents` that has one,
+    /// as `remove_component`, dropping the removed components. Returns how many
+    /// were removed.
+    /// 
+    /// # Panics
+    /// Panics if any of the components is currently borrowed.
+    pub fn bulk_remove_component<T: component::Component>(&self, ents: &[Entity]) -> usize {
+        ents.iter().filter(|&&ent| self.remove_component::<T>(ent).is_some()).count()
+    }
+
+    /// Removes the component of type `T` from entity `ent` and returns it, or
+    /// `None` if the entity is invalid or has no `T`.
+    /// 
+    /// # Panics
+    /// Panics if the component is currently borrowed, or if any component the
+    /// entity was given after it is.
+    pub fn remove_component<T: component::Component>(&self, ent: Entity) -> Option<T> {
+        self.remove_component_dyn(ent, TypeId::of::<T>())
+            .map(|component| *component.downcast::<T>().unwrap())
+    }
+
+    /// Removes the component of type `ty` from entity `ent` and returns it
+    /// boxed, like `remove_component`
+    /// 
+    /// # Panics
+    /// Panics if the component is currently borrowed, or if any component the
+    /// entity was given after it is.
+    pub fn remove_component_dyn(&self, ent: Entity, ty: TypeId) -> Option<Box<dyn Any>> {
+        if !self.is_valid(ent) {
+            return None;
+        }
+        check_structural();
+
+        let mut components = self.entities[ent].borrow_mut();
+        let removed = components.remove(ty)?;
+
+        self.archetypes.borrow_mut().relocate(ent, &components);
+        self.structure_changed();
+        if components.record(ty, Event::Removed) {
+            self.evented_ents.borrow_mut().push(ent);
+        }
+
+        drop(components);
+        Some(unsafe { self.take_component((ty, removed)) })
+    }
+
+    /// Exchanges the components of type `T` held by entities `a` and `b`, returning
+    /// `false` if either entity is invalid or lacks a `T`. Only the pointers to the
+    /// components are swapped, the values themselves never move.
+    pub fn swap_components<T: component::Component>(&mut self, a: Entity, b: Entity) -> bool {
+        if !self.has_component::<T>(a) || !self.has_component::<T>(b) {
+            return false;
+        }
+
+        if a != b {
+            let ty = TypeId::of::<T>();
+            let mut components_a = self.entities[a].borrow_mut();
+            let mut components_b = self.entities[b].borrow_mut();
+
+            let ptr_a = components_a.get(ty).unwrap();
+            let ptr_b = components_b.insert((ty, ptr_a)).unwrap();
+            components_a.insert((ty, ptr_b));
+
+            if components_a.mark_listed() {
+                self.changed_ents.get_mut().push(a);
+            }
+            if components_b.mark_listed() {
+                self.changed_ents.get_mut().push(b);
+            }
+        }
+
+        true
+    }
+
+    /// Get the component of type `T` from entity `ent`, like `get_component`,
+    /// but saying why it couldn't be borrowed instead of returning `None` or
+    /// panicking.
+    pub fn try_get_component<T: component::Component>(&self, ent: Entity) -> Result<ComponentMut<'_, T>, ComponentError> {
+        if !self.is_valid(ent) {
+            return Err(ComponentError::InvalidEntity(ent));
+        }
+        check_write::<T>();
+
+        let type_name = any::type_name::<T>();
+        let borrowed = {
+            let components = self.entities[ent].borrow();
+            match components.position(TypeId::of::<T>()) {
+                Some(i) => components.flag(i).is_borrowed(),
+                None => return Err(ComponentError::ComponentMissing { type_name })
+            }
+        };
+        if borrowed {
+            return Err(ComponentError::AlreadyBorrowed { type_name });
+        }
+
+        ComponentMut::new(&self.entities[ent], ent, &self.changed_ents).ok_or(ComponentError::ComponentMissing { type_name })
+    }
+
+    /// Get the component of type `T` from entity `ent`. The component stays
+    /// mutably borrowed until the returned guard is dropped.
+    /// 
+    /// # Panics
+    /// Panics if the component is already borrowed, for instance by a guard
+    /// returned from an earlier call that is still alive.
+    pub fn get_component<T: component::Component>(&self, ent: Entity) -> Option<ComponentMut<'_, T>> {
+        check_write::<T>();
+        match self.valid_ents.get(ent).map(Cell::get) {
+            Some(true) => ComponentMut::new(&self.entities[ent], ent, &self.changed_ents),
+            _ => None
+        }
+    }
+
+    /// Mutably borrows entity `ent`'s component of type `T`, first adding the
+    /// result of `f` if the entity has no `T`. Returns `None` only if `ent` is
+    /// invalid.
+    /// 
+    /// # Panics
+    /// Panics if the component is currently borrowed.
+    pub fn get_or_insert_component<T, F>(&self, ent: Entity, f: F) -> Option<ComponentMut<'_, T>>
+        where T: component::Component, F: FnOnce() -> T {
+        if !self.is_valid(ent) {
+            return None;
+        }
+
+        let present = self.entities[ent].borrow().contains(TypeId::of::<T>());
+        if !present {
+            self.add_component(ent, f());
+        }
+
+        ComponentMut::new(&self.entities[ent], ent, &self.changed_ents)
+    }
+
+    /// Mutably borrows entity `ent`'s component of type `T`, first adding
+    /// `T::default()` if the entity has no `T`. Returns `None` only if `ent` is
+    /// invalid.
+    /// 
+    /// # Panics
+    /// Panics if the component is currently borrowed.
+    pub fn get_component_or_default<T>(&self, ent: Entity) -> Option<ComponentMut<'_, T>>
+        where T: component::Component + Default {
+        self.get_or_insert_component(ent, T::default)
+    }
+
+    /// Get the components of types `A` and `B` from entity `ent` at once, or
+    /// `None` if it lacks either or `A` and `B` are the same type.
+    /// 
+    /// # Panics
+    /// Panics if either component is already borrowed.
+    pub fn get_two_mut<A, B>(&self, ent: Entity) -> Option<(ComponentMut<'_, A>, ComponentMut<'_, B>)>
+        where A: component::Component, B: component::Component {
+        if TypeId::of::<A>() == TypeId::of::<B>() {
+            return None;
+        }
+
+        let a = self.get_component::<A>(ent)?;
+        let b = self.get_component::<B>(ent)?;
+
+        Some((a, b))
+    }
+
+    /// Get the components of types `A`, `B` and `C` from entity `ent` at once,
+    /// or `None` if it lacks any of them or any two are the same type.
+    /// 
+    /// # Panics
+    /// Panics if any of the components is already borrowed.
+    #[allow(clippy::type_complexity)]
+    pub fn get_three_mut<A, B, C>(&self, ent: Entity) -> Option<(ComponentMut<'_, A>, ComponentMut<'_, B>, ComponentMut<'_, C>)>
+        where A: component::Component, B: component::Component, C: component::Component {
+        if TypeId::of::<A>() == TypeId::of::<C>() || TypeId::of::<B>() == TypeId::of::<C>() {
+            return None;
+        }
+
+        let (a, b) = self.get_two_mut::<A, B>(ent)?;
+        let c = self.get_component::<C>(ent)?;
+
+        Some((a, b, c))
+    }
+
+    /// Gets a pointer to entity `ent`'s component of type `ty`, for components
+    /// whose type is only known at runtime. The pointer stays valid until the
+    /// component is replaced or removed, or the entity is dropped.
+    /// 
+    /// Unlike `get_component`, the access isn't borrow tracked: the caller must
+    /// make sure it doesn't alias a `ComponentRef` or `ComponentMut` to the same
+    /// component when dereferencing the pointer.
+    pub fn get_component_dyn(&self, ent: Entity, ty: TypeId) -> Option<*mut dyn Any> {
+        if !self.is_valid(ent) {
+            return None;
+        }
+
+        self.component_ptr(ent, ty)
+    }
+
+    /// Get a shared reference to the component of type `T` from entity `ent`.
+    /// Any number of shared references to a component can be held at once, but
+    /// while any of them is alive the component can't be borrowed mutably with
+    /// [`get_component`](#method.get_component). Prefer this over `get_component`
+    /// for read-only access, so that readers don't block each other.
+    /// 
+    /// # Panics
+    /// Panics if the component is already mutably borrowed.
+    pub fn get_component_ref<T: component::Component>(&self, ent: Entity) -> Option<ComponentRef<'_, T>> {
+        check_read::<T>();
+        match self.valid_ents.get(ent).map(Cell::get) {
+            Some(true) => ComponentRef::new(&self.entities[ent]),
+            _ => None
+        }
+    }
+
+    /// Check whether entity `ent` has a component of type `T`
+    pub fn has_component<T: component::Component>(&self, ent: Entity) -> bool {
+        match self.valid_ents.get(ent).map(Cell::get) {
+            Some(true) => {
+                let ty = TypeId::of::<T>();
+                self.component_ptr(ent, ty).is_some()
+            },
+            _ => false
+        }
+    }
+
+    /// Check whether entity `ent` has a component of every type in `types`, for
+    /// when the types are only known at runtime. Invalid entities have none.
+    pub fn has_components(&self, ent: Entity, types: &[TypeId]) -> bool {
+        match self.valid_ents.get(ent).map(Cell::get) {
+            Some(true) => {
+                let components = self.entities[ent].borrow();
+                types.iter().all(|&ty| components.contains(ty))
+            },
+            _ => false
+        }
+    }
+
+    /// Check whether entity `ent` has a component of any type in `types`
+    pub fn has_any_component(&self, ent: Entity, types: &[TypeId]) -> bool {
+        match self.valid_ents.get(ent).map(Cell::get) {
+            Some(true) => {
+                let components = self.entities[ent].borrow();
+                types.iter().any(|&ty| components.contains(ty))
+            },
+            _ => false
+        }
+    }
+
+    /// Check whether entity `ent` currently matches `query`, for per-entity
+    /// logic outside of a system. Invalid entities match nothing.
+    pub fn matches(&self, ent: Entity, query: &Query) -> bool {
+        match self.valid_ents.get(ent).map(Cell::get) {
+            Some(true) => query.matches(&self.entities[ent]),
+            _ => false
+        }
+    }
+
+    /// Lists the types of every component held by entity `ent`, in the order they
+    /// were added. Invalid entities have no components.
+    pub fn components_of(&self, ent: Entity) -> Vec<TypeId> {
+        match self.valid_ents.get(ent).map(Cell::get) {
+            Some(true) => self.entities[ent].borrow().iter().map(|&(ty, _)| ty).collect(),
+            _ => Vec::new()
+        }
+    }
+
+    /// Lists the type names of every component held by entity `ent`, in the
+    /// order they were added, for display in tools such as inspectors. Names are
+    /// recorded by `add_component`; components added with `add_component_dyn`
+    /// whose type was never added with `add_component` are listed as `"?"`.
+    /// Invalid entities have no components.
+    /// 
+    /// Only available with the `debug-names` feature, which costs a lookup for
+    /// every component added.
+    #[cfg(feature = "debug-names")]
+    pub fn type_name_of_components(&self, ent: Entity) -> Vec<&'static str> {
+        let names = self.type_names.borrow();
+        self.components_of(ent).iter().map(|ty| names.get(ty).cloned().unwrap_or("?")).collect()
+    }
+
+    /// Checks the world's storage for inconsistencies, returning every one found.
+    /// Meant for tests and debugging, after a sequence of operations that may
+    /// have left the world in a bad state; nothing here should ever be found.
+    pub fn validate(&self) -> Result<(), Vec<WorldError>> {
+        let mut errors = Vec::new();
+        if self.entities.len() != self.valid_ents.len() {
+            errors.push(WorldError::LengthMismatch {
+                entities: self.entities.len(),
+                valid: self.valid_ents.len()
+            });
+        }
+
+        for (ent, entity) in self.entities.iter().enumerate() {
+            let components = entity.borrow();
+            for &(ty, ptr) in components.iter() {
+                if ptr.is_null() {
+                    errors.push(WorldError::NullComponent(ent, ty));
+                }
+            }
+
+            let mut types: Vec<TypeId> = components.iter().map(|&(ty, _)| ty).collect();
+            types.sort();
+            for (i, pair) in types.windows(2).enumerate() {
+                // Each duplicated type is reported once, however many times it's held
+                let repeated = i > 0 && types[i - 1] == pair[0];
+                if pair[0] == pair[1] && !repeated {
+                    errors.push(WorldError::DuplicateComponent(ent, pair[0]));
+                }
+            }
+        }
+
+        for &ent in self.free_ents.iter() {
+            if self.is_valid(ent) {
+                errors.push(WorldError::LiveEntityFreed(ent));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Whether `ent` is a live entity
+    #[inline]
+    pub(crate) fn is_valid(&self, ent: Entity) -> bool {
+        self.valid_ents.get(ent).is_some_and(Cell::get)
+    }
+
+    /// Looks up the pointer to `ent`'s component of type `ty` without touching the
+    /// entity's borrow flag, so that systems run by `process_parallel` can look up
+    /// components from several threads at once.
+    fn component_ptr(&self, ent: Entity, ty: TypeId) -> Option<*mut dyn Any> {
+        // The reference never outlives this call, and nothing mutably borrows an
+        // entity's components while calling out to other code.
+        unsafe {
+            self.entities[ent].try_borrow_unguarded().ok().and_then(|components| components.get(ty))
+        }
+    }
+
+    /// Stores `resource` as the world's single value of type `T`, returning the
+    /// value it replaced if there was one.
+    pub fn insert_resource<T: Any>(&mut self, resource: T) -> Option<T> {
+        self.resources.insert(TypeId::of::<T>(), Box::new(resource))
+            .map(|old| *old.downcast::<T>().unwrap())
+    }
+
+    /// Get the world's resource of type `T`
+    pub fn get_resource<T: Any>(&self) -> Option<&T> {
+        self.resources.get(&TypeId::of::<T>()).and_then(|r| r.downcast_ref::<T>())
+    }
+
+    /// Get the world's resource of type `T` mutably
+    pub fn get_resource_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut(&TypeId::of::<T>()).and_then(|r| r.downcast_mut::<T>())
+    }
+
+    /// Removes the world's resource of type `T` and returns it
+    pub fn remove_resource<T: Any>(&mut self) -> Option<T> {
+        self.resources.remove(&TypeId::of::<T>())
+            .map(|old| *old.downcast::<T>().unwrap())
+    }
+
+    /// Runs a tick in which `dt` time has elapsed since the last one. Systems can
+    /// read the elapsed time and the index of the tick from the world's
+    /// [`FrameContext`](struct.FrameContext.html) resource.
+    pub fn process_dt(&mut self, dt: f32) {
+        self.tick_dt(dt);
+    }
+
+    /// Runs a tick like `process`, returning counts of the work done.
+    /// 
+    /// ```
+    /// use apollo_ecs::*;
+    /// 
+    /// let mut world = World::new();
+    /// let ent = world.create_entity();
+    /// world.remove_entity(ent);
+    /// 
+    /// let stats = world.tick();
+    /// assert_eq!(stats.systems_run, 0);
+    /// assert_eq!(stats.entities_despawned, 1);
+    /// ```
+    pub fn tick(&mut self) -> TickStats {
+        self.tick_dt(0.0)
+    }
+
+    /// Runs a tick like `process_dt`, returning counts of the work done
+    pub fn tick_dt(&mut self, dt: f32) -> TickStats {
+        let mut stats = TickStats::default();
+
+        self.begin_frame(dt);
+        if !self.startup_systems.is_empty() {
+            self.run_startup();
+        }
+
+        for &i in self.order.iter() {
+            if let Some(visited) = self.run_system(&self.systems[i]) {
+                stats.entities_visited += visited;
+                stats.systems_run += 1;
+                stats.per_system.push((self.system_handle(i), self.systems[i].name(), visited));
+            }
+        }
+
+        stats.entities_despawned = self.flush_dead_entities();
+        self.end_frame();
+
+        stats
+    }
+
+    /// The main loop for a world. Calling `process` runs all ready systems in this world.
+    /// 
+    /// Each system visits only the entities of archetypes its query matches, rather
+    /// than testing every entity in the world. The matched entities are cached per
+    /// system and only gathered again once the world's structure has changed, so
+    /// components added while systems are running are seen by a system the next
+    /// time it runs.
+    /// 
+    /// Every system visits its entities in ascending order, whatever order they
+    /// were created or given components in, so worlds built up and changed the
+    /// same way run their systems the same way. Pairwise systems are given pairs
+    /// in ascending order of their first, then second, entity.
+    /// 
+    /// `process` is equivalent to `process_dt(0.0)`.
+    pub fn process(&mut self) {
+        self.process_dt(0.0);
+    }
+
+    /// Runs only the system identified by `handle` over the entities its query
+    /// matches, then drops any entities removed while it ran. Unlike `process`
+    /// this doesn't start a new tick: startup systems aren't run, the
+    /// [`FrameContext`](struct.FrameContext.html) isn't advanced and changed
+    /// components stay marked as changed. A disabled system, or one whose run
+    /// criteria return false, is skipped.
+    /// 
+    /// Returns whether the system ran, which it doesn't if it was skipped or
+    /// `handle` came from another world.
+    pub fn process_system(&mut self, handle: SystemHandle) -> bool {
+        let ran = match self.system_index(handle) {
+            Some(i) => self.run_system(&self.systems[i]).is_some(),
+            None => false
+        };
+        self.flush_dead_entities();
+
+        ran
+    }
+
+    /// Equivalent to `process_parallel_dt(0.0)`.
+    /// 
+    /// # Safety
+    /// See [`process_parallel_dt`](#method.process_parallel_dt).
+    #[cfg(feature = "parallel")]
+    pub unsafe fn process_parallel(&mut self) {
+        self.process_parallel_dt(0.0);
+    }
+
+    /// Runs a tick like `process_dt`, but on a thread pool. Systems are split, in
+    /// registration order, into batches of systems whose declared `reads` and
+    /// `writes` don't overlap, and the systems of each batch run alongside each
+    /// other, each batch completing before the next begins. Systems that don't
+    /// declare `writes` are assumed to write everything and always run on their
+    /// own. Parallel systems also split their matched entities across the pool.
+    /// 
+    /// In debug builds, a system run on the pool panics if it borrows a component
+    /// type it hasn't declared, or adds or removes entities or components.
+    /// 
+    /// # Safety
+    /// The world's storage is not synchronized, so the caller must guarantee that
+    /// while a batch with more than one system, or a parallel system, is running:
+    /// 
+    /// * every system, and every component type accessed, is safe to send to
+    ///   another thread;
+    /// * systems only access the component types they declare, and only through
+    ///   `get_component`, `get_component_ref`, `has_component` and `get_resource`;
+    /// * systems make no structural changes, such as `add_component` or
+    ///   `remove_entity`.
+    /// 
+    /// Only the first of these goes unchecked in debug builds.
+    #[cfg(feature = "parallel")]
+    pub unsafe fn process_parallel_dt(&mut self, dt: f32) {
+        self.begin_frame(dt);
+        if !self.startup_systems.is_empty() {
+            self.run_startup();
+        }
+
+        let ordered: Vec<&SystemEntry> = self.order.iter().map(|&i| &self.systems[i]).collect();
+        let batches = schedule::batches(ordered.iter().map(|sys| &sys.access));
+        for batch in batches {
+            let systems: Vec<&SystemEntry> = ordered[batch].iter()
+                .cloned()
+                .filter(|sys| self.should_run(sys))
+                .collect();
+            // Nothing else runs alongside a lone iterative or pairwise system
+            if systems.len() == 1 && !systems[0].is_parallel() {
+                self.run_system(systems[0]);
+                continue;
+            }
+
+            for sys in systems.iter() {
+                self.refresh_matches(sys);
+                self.select_candidates(sys);
+            }
+
+            let world = Shared(self as *const World);
+            let systems: Vec<Shared<SystemEntry>> = systems.into_iter().map(|sys| Shared(sys as *const SystemEntry)).collect();
+            rayon::scope(|scope| {
+                for sys in systems.iter() {
+                    let world = &world;
+                    scope.spawn(move |_| (*world.0).run_shared(&*sys.0));
+                }
+            });
+        }
+
+        self.flush_dead_entities();
+        self.end_frame();
+    }
+
+    /// Calls `sys` for every entity in its cached matches, as `run_matches`, from
+    /// a thread of the pool and under the system's declared access. The entities
+    /// of a parallel system are split across the pool.
+    #[cfg(feature = "parallel")]
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn run_shared(&self, sys: &SystemEntry) {
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::debug_span!("system", name = sys.type_name).entered();
+
+        let visited = if sys.is_parallel() {
+            self.run_split(sys)
+        } else {
+            schedule::with_access(&sys.access, || self.run_matches(sys))
+        };
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(matched = visited, "system ran");
+    }
+
+    /// Calls parallel system `sys` for every entity in its cached matches,
+    /// split across the pool, returning how many entities were visited
+    #[cfg(feature = "parallel")]
+    fn run_split(&self, sys: &SystemEntry) -> usize {
+        let system = sys.system.borrow();
+        let system = match *system {
+            System::Parallel(ref system) => system,
+            _ => unreachable!()
+        };
+
+        let matches = sys.matches.borrow();
+        let ents: Vec<Entity> = Merged(&matches.entities, &matches.selected).collect();
+        let access = &sys.access;
+        let world = Shared(self as *const World);
+        ents.par_iter().filter(|&&ent| {
+            // The world outlives the scope `process_parallel` runs this in
+            let world = unsafe { &*world.0 };
+            if !world.valid_ents[ent].get() {
+                return false;
+            }
+
+            schedule::with_access(access, || system.process(ent, world));
+            true
+        }).count()
+    }
+
+    fn begin_frame(&mut self, dt: f32) {
+        if let Some(ctx) = self.get_resource_mut::<FrameContext>() {
+            ctx.dt = dt;
+        }
+
+        // Reused entities can be listed more than once
+        let mut ents = mem::take(self.evented_ents.get_mut());
+        ents.sort();
+        ents.dedup();
+        ents.retain(|&ent| self.entities[ent].get_mut().rotate_events());
+        *self.evented_ents.get_mut() = ents;
+    }
+
+    fn end_frame(&mut self) {
+        let mut ents = mem::take(self.changed_ents.get_mut());
+        for ent in ents.drain(..) {
+            self.entities[ent].get_mut().clear_changed();
+        }
+        // Kept for its allocation
+        *self.changed_ents.get_mut() = ents;
+
+        if let Some(ctx) = self.get_resource_mut::<FrameContext>() {
+            ctx.frame += 1;
+        }
+    }
+
+    /// Drops every entity scheduled for removal with `remove_entity`, returning
+    /// how many there were
+    fn flush_dead_entities(&mut self) -> usize {
+        let mut dropped = 0;
+        loop {
+            let dead_ent = self.dead_ents.borrow_mut().pop_front();
+            match dead_ent {
+                Some(ent) => self.free_entity(ent),
+                None => return dropped
+            }
+            dropped += 1;
+        }
+    }
+
+    /// Appends every live entity matching `query` to `out`, testing archetypes
+    /// as a whole where possible.
+    fn matching_entities(&self, query: &Query, out: &mut Vec<Entity>) {
+        let archetypes = self.archetypes.borrow();
+        for id in 0..archetypes.len() {
+            let archetype = archetypes.get(id);
+            match query.test_signature(&archetype.signature) {
+                Some(true) => out.extend(archetype.entities.iter().filter(|&&ent| self.valid_ents[ent].get())),
+                Some(false) => (),
+                None => {
+                    for &ent in archetype.entities.iter() {
+                        if self.valid_ents[ent].get() && query.matches(&self.entities[ent]) {
+                            out.push(ent);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether `sys` is enabled, and has no run criteria or they allow it to run
+    /// this tick
+    fn should_run(&self, sys: &SystemEntry) -> bool {
+        sys.enabled && sys.run_criteria.as_ref().is_none_or(|criteria| criteria(self))
+    }
+
+    /// Calls `sys` for every entity its query currently matches, returning how
+    /// many were visited, or `None` if the system was skipped this tick
+    fn run_system(&self, sys: &SystemEntry) -> Option<usize> {
+        if !self.should_run(sys) {
+            return None;
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::debug_span!("system", name = sys.type_name).entered();
+
+        let visited = self.refresh_and_run(sys);
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(matched = visited, "system ran");
+
+        Some(visited)
+    }
+
+    /// Brings the matches of `sys` up to date and calls it for each of them,
+    /// returning how many entities were visited
+    fn refresh_and_run(&self, sys: &SystemEntry) -> usize {
+        self.refresh_matches(sys);
+        {
+            // Systems matching nothing are common enough, and cheap enough to
+            // spot, not to bother testing candidates or borrowing the system
+            let matches = sys.matches.borrow();
+            if matches.entities.is_empty() && matches.candidates.is_empty() {
+                return 0;
+            }
+        }
+
+        self.select_candidates(sys);
+        self.run_matches(sys)
+    }
+
+    /// Calls `sys` for every entity in its cached matches, and every candidate
+    /// selected when they were last tested, in ascending order and skipping any
+    /// removed since. Pairwise systems are instead called for each pair of those
+    /// entities. Returns the number of entities visited.
+    fn run_matches(&self, sys: &SystemEntry) -> usize {
+        let matches = sys.matches.borrow();
+        let ents = Merged(&matches.entities, &matches.selected);
+
+        let mut visited = 0;
+        match *sys.system.borrow_mut() {
+            System::Iterative(ref mut system) => {
+                for ent in ents {
+                    if self.valid_ents[ent].get() {
+                        system.process(ent, self);
+                        visited += 1;
+                    }
+                }
+            },
+            System::Parallel(ref system) => {
+                for ent in ents {
+                    if self.valid_ents[ent].get() {
+                        system.process(ent, self);
+                        visited += 1;
+                    }
+                }
+            },
+            System::Pairwise(ref mut system) => {
+                let ents: Vec<Entity> = ents.collect();
+                for (i, &a) in ents.iter().enumerate() {
+                    if self.valid_ents[a].get() {
+                        visited += 1;
+                    }
+                    for &b in ents[i + 1..].iter() {
+                        if self.valid_ents[a].get() && self.valid_ents[b].get() {
+                            system.process(a, b, self);
+                        }
+                    }
+                }
+            },
+            System::Unregistered => ()
+        }
+
+        visited
+    }
+
+    /// Tests each entity that may match `sys` depending on its component values.
+    /// Unlike the cached matches, this has to happen every time the system runs.
+    fn select_candidates(&self, sys: &SystemEntry) {
+        let mut matches = sys.matches.borrow_mut();
+        let matches = &mut *matches;
+
+        matches.selected.clear();
+        for &ent in matches.candidates.iter() {
+            if self.valid_ents[ent].get() && sys.query.matches(&self.entities[ent]) {
+                matches.selected.push(ent);
+            }
+        }
+    }
+
+    /// Marks the set of entities, or the components they hold, as changed,
+    /// invalidating every system's cached matches. Inside `apply` the change is
+    /// only noted, until the version is next needed.
+    #[inline]
+    fn structure_changed(&self) {
+        if self.batch_depth > 0 {
+            self.batched_change.set(true);
+        } else {
+            self.structure_version.set(self.structure_version.get() + 1);
+        }
+    }
+
+    /// The current structure version, counting any changes noted inside `apply`
+    /// as a single one
+    fn current_version(&self) -> u64 {
+        if self.batched_change.replace(false) {
+            self.structure_version.set(self.structure_version.get() + 1);
+        }
+
+        self.structure_version.get()
+    }
+
+    /// Rebuilds the cached entities matched by `sys` if the world's structure
+    /// has changed since they were last gathered, first testing any archetypes
+    /// created in the meantime.
+    fn refresh_matches(&self, sys: &SystemEntry) {
+        let version = self.current_version();
+        let mut matches = sys.matches.borrow_mut();
+        if matches.version == Some(version) {
+            return;
+        }
+
+        let archetypes = self.archetypes.borrow();
+        let matches = &mut *matches;
+        while matches.checked < archetypes.len() {
+            let id = matches.checked;
+            match sys.query.test_signature(&archetypes.get(id).signature) {
+                Some(true) => matches.archetypes.push((id, true)),
+                None => matches.archetypes.push((id, false)),
+                Some(false) => ()
+            }
+
+            matches.checked += 1;
+        }
+
+        matches.entities.clear();
+        matches.candidates.clear();
+        for &(id, exact) in matches.archetypes.iter() {
+            if exact {
+                matches.entities.extend_from_slice(&archetypes.get(id).entities);
+            } else {
+                matches.candidates.extend_from_slice(&archetypes.get(id).entities);
+            }
+        }
+        // Archetypes don't keep their entities in order, and entities move
+        // between them
+        matches.entities.sort_unstable();
+        matches.candidates.sort_unstable();
+
+        matches.version = Some(version);
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
+    }
+}
+
+/// The most live entities listed by `World`'s `Debug` output
+const DEBUG_ENTITIES: usize = 16;
+
+impl fmt::Debug for World {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("World")
+            .field("live_entities", &self.iter_entities().count())
+            .field("free_entities", &self.free_ents.len())
+            .field("dead_entities", &self.dead_ents.borrow().len())
+            .field("systems", &self.system_count())
+            .field("entities", &DebugEntities(self))
+            .finish()
+    }
+}
+
+/// Lists the component types of the first few live entities in a world
+struct DebugEntities<'a>(&'a World);
+
+impl<'a> fmt::Debug for DebugEntities<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut map = f.debug_map();
+        let mut ents = self.0.iter_entities();
+        for ent in ents.by_ref().take(DEBUG_ENTITIES) {
+            map.entry(&ent, &self.0.components_of(ent));
+        }
+
+        if ents.next().is_some() {
+            map.finish_non_exhaustive()
+        } else {
+            map.finish()
+        }
+    }
+}
+
+#[cfg(test)]
 mod test {
     use super::*;
     
     #[test]
-    fn test_create_entity() {
+    fn test_create_entity() {
+        let mut world = World::new();
+        world.create_entity();
+
+        assert_eq!(world.entities.len(), 1);
+    }
+    
+    #[test]
+    fn test_drop_entity() {
+        let mut world = World::new();
+        let ent = world.create_entity();
+
+        assert_eq!(world.entities.len(), 1);
+
+        world.drop_entity(ent);
+        assert_eq!(world.entities.len(), 1);
+        assert_eq!(world.valid_ents[ent].get(), false);
+    }
+
+    #[test]
+    fn test_drop_entity_clears_components() {
+        use query::Matchers;
+
+        struct A(u32);
+
+        let mut world = World::new();
+        let dropped = world.create_entity();
+        world.add_component(dropped, A(1));
+        let removed = world.create_entity();
+        world.add_component(removed, A(2));
+        let kept = world.create_entity();
+        world.add_component(kept, A(3));
+
+        world.drop_entity(dropped);
+        world.remove_entity(removed);
+        world.process();
+
+        // Nothing is left behind pointing at the freed components, so even
+        // testing the storage directly can't reach them
+        let query = Matchers::with::<A>().build();
+        for &ent in [dropped, removed].iter() {
+            assert!(world.entities[ent].borrow().is_empty());
+            assert!(!query.matches(&world.entities[ent]));
+        }
+        assert_eq!(world.filter_entities(Matchers::with::<A>()).collect::<Vec<_>>(), vec!(kept));
+        assert_eq!(world.iter_components::<A>().map(|(_, a)| a.0).collect::<Vec<_>>(), vec!(3));
+    }
+
+    #[test]
+    fn test_remove_entity() {
+        let mut world = World::new();
+        let ent = world.create_entity();
+
+        assert_eq!(world.entities.len(), 1);
+
+        world.remove_entity(ent);
+        world.process();
+        assert_eq!(world.entities.len(), 1);
+        assert_eq!(world.valid_ents[ent].get(), false);
+    }
+
+    #[test]
+    fn test_double_removal() {
+        use std::rc::Rc;
+
+        let drops = Rc::new(());
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, drops.clone());
+
+        world.remove_entity(ent);
+        world.remove_entity(ent);
+        world.drop_entity(ent);
+        world.drop_entity(ent);
+        assert_eq!(Rc::strong_count(&drops), 1);
+
+        // The ID is only handed out once, and the removals queued above don't
+        // reach the entity reusing it
+        let reused = world.create_entity();
+        assert_eq!(reused, ent);
+        world.add_component(reused, drops.clone());
+        assert_ne!(world.create_entity(), ent);
+
+        world.process();
+        assert!(world.has_component::<Rc<()>>(reused));
+        assert_eq!(Rc::strong_count(&drops), 2);
+    }
+
+    #[test]
+    fn test_iteration_order() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A;
+        struct B(bool);
+        struct Unrelated;
+
+        struct Recorder(Rc<RefCell<Vec<Entity>>>);
+
+        impl IterativeSystem for Recorder {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>().and(Matchers::without::<B>().or(Matchers::where_component(|b: &B| b.0))))
+            }
+
+            fn process(&mut self, ent: Entity, _: &World) {
+                self.0.borrow_mut().push(ent);
+            }
+        }
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::new();
+        world.register_iterative_system(Recorder(seen.clone()));
+
+        // Entities with a `B` only match depending on its value, so are tested
+        // separately from the rest
+        for i in 0..6 {
+            let ent = world.create_entity();
+            if i % 2 == 1 {
+                world.add_component(ent, B(i != 3));
+            }
+            world.add_component(ent, A);
+        }
+
+        let run = |world: &mut World| {
+            seen.borrow_mut().clear();
+            world.process();
+            seen.borrow().clone()
+        };
+
+        assert_eq!(run(&mut world), vec!(0, 1, 2, 4, 5));
+        assert_eq!(run(&mut world), vec!(0, 1, 2, 4, 5));
+
+        // Moving entities between archetypes and dropping others reorders the
+        // archetypes' own lists
+        world.remove_component::<A>(0);
+        world.add_component(0, A);
+        world.drop_entity(2);
+        let ent = world.create_entity();
+        world.add_component(ent, Unrelated);
+        assert_eq!(run(&mut world), vec!(0, 1, 4, 5));
+    }
+
+    #[test]
+    fn test_on_despawn() {
+        use std::rc::Rc;
+
+        struct GpuHandle(u32);
+
+        let released = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::new();
+        {
+            let released = released.clone();
+            world.on_despawn(move |ent, world| {
+                if let Some(handle) = world.get_component_ref::<GpuHandle>(ent) {
+                    released.borrow_mut().push(handle.0);
+                }
+                // Removing a dying entity again does nothing
+                world.remove_entity(ent);
+            });
+        }
+
+        let ents: Vec<Entity> = (0..4).map(|i| world.spawn().add(GpuHandle(i * 10)).id()).collect();
+        world.remove_entity(ents[1]);
+        assert_eq!(*released.borrow(), vec!());
+
+        world.process();
+        assert_eq!(*released.borrow(), vec!(10));
+        assert!(world.get_component_ref::<GpuHandle>(ents[1]).is_none());
+
+        world.drop_entity(ents[2]);
+        world.process();
+        assert_eq!(*released.borrow(), vec!(10, 20));
+        assert_eq!(world.free_ents.len(), 2);
+
+        world.clear();
+        assert_eq!(*released.borrow(), vec!(10, 20, 0, 30));
+    }
+
+    #[test]
+    fn test_removed_entity_skipped_mid_tick() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A(u32);
+
+        struct Reaper;
+        struct Reader(Rc<RefCell<Vec<u32>>>);
+
+        impl IterativeSystem for Reaper {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                if world.get_component::<A>(ent).unwrap().0 == 1 {
+                    world.remove_entity(ent);
+                    assert!(world.get_component::<A>(ent).is_none());
+                }
+            }
+        }
+
+        impl IterativeSystem for Reader {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                self.0.borrow_mut().push(world.get_component::<A>(ent).unwrap().0);
+            }
+        }
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::new();
+        world.register_iterative_system(Reaper);
+        world.register_iterative_system(Reader(seen.clone()));
+
+        for i in 0..3 {
+            let ent = world.create_entity();
+            world.add_component(ent, A(i));
+        }
+
+        world.process();
+        assert_eq!(*seen.borrow(), vec!(0, 2));
+
+        // Removing the same entity twice only frees it once
+        world.remove_entity(0);
+        world.remove_entity(0);
+        world.process();
+        assert_eq!(world.free_ents.len(), 2);
+    }
+
+    #[test]
+    fn test_add_duplicate_component() {
+        use std::rc::Rc;
+
+        struct A(u32, Rc<Cell<usize>>);
+
+        impl Drop for A {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let dropped = Rc::new(Cell::new(0));
+        let mut world = World::new();
+        let ent = world.create_entity();
+
+        assert!(world.add_component(ent, A(1, dropped.clone())));
+        assert!(world.add_component(ent, A(2, dropped.clone())));
+        assert_eq!(dropped.get(), 1);
+        assert_eq!(world.entities[ent].borrow().len(), 1);
+        assert_eq!(world.get_component::<A>(ent).unwrap().0, 2);
+
+        world.drop_entity(ent);
+        assert_eq!(dropped.get(), 2);
+    }
+
+    #[test]
+    fn test_bulk_add_component() {
+        #[derive(Clone)]
+        struct Selected;
+
+        let mut world = World::new();
+        let ents: Vec<Entity> = (0..5).map(|_| world.create_entity()).collect();
+        world.drop_entity(ents[3]);
+
+        assert_eq!(world.bulk_add_component(&[ents[0], ents[1], ents[3], ents[4], 100], Selected), 3);
+        assert!(world.has_component::<Selected>(ents[0]));
+        assert!(world.has_component::<Selected>(ents[1]));
+        assert!(world.has_component::<Selected>(ents[4]));
+        assert!(!world.has_component::<Selected>(ents[2]));
+    }
+
+    #[test]
+    fn test_bulk_remove_component() {
+        #[derive(Clone)]
+        struct Selected;
+
+        let mut world = World::new();
+        let ents: Vec<Entity> = (0..6).map(|_| world.create_entity()).collect();
+        world.bulk_add_component(&ents[..4], Selected);
+        world.drop_entity(ents[1]);
+
+        assert_eq!(world.bulk_remove_component::<Selected>(&[ents[0], ents[1], ents[2], ents[5]]), 2);
+        assert_eq!(world.count_components::<Selected>(), 1);
+        assert!(!world.has_component::<Selected>(ents[0]));
+        assert!(!world.has_component::<Selected>(ents[2]));
+        assert!(world.has_component::<Selected>(ents[3]));
+    }
+
+    #[test]
+    fn test_try_get_component() {
+        use std::any::type_name;
+
+        struct Health(u32);
+        struct Armor;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, Health(10));
+
+        world.try_get_component::<Health>(ent).unwrap().0 -= 3;
+        assert_eq!(world.get_component_ref::<Health>(ent).unwrap().0, 7);
+
+        let missing = world.try_get_component::<Armor>(ent).err();
+        assert_eq!(missing, Some(ComponentError::ComponentMissing { type_name: type_name::<Armor>() }));
+
+        {
+            let _health = world.get_component_ref::<Health>(ent).unwrap();
+            let borrowed = world.try_get_component::<Health>(ent).err();
+            assert_eq!(borrowed, Some(ComponentError::AlreadyBorrowed { type_name: type_name::<Health>() }));
+        }
+
+        world.remove_entity(ent);
+        assert_eq!(world.try_get_component::<Health>(ent).err(), Some(ComponentError::InvalidEntity(ent)));
+        assert_eq!(ComponentError::InvalidEntity(ent).to_string(), format!("entity {} is not live", ent));
+    }
+
+    #[test]
+    fn test_get_or_insert_component() {
+        struct Count(u32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        let mut calls = 0;
+
+        for _ in 0..2 {
+            let mut count = world.get_or_insert_component(ent, || {
+                calls += 1;
+                Count(0)
+            }).unwrap();
+            count.0 += 1;
+        }
+
+        assert_eq!(calls, 1);
+        assert_eq!(world.get_component_ref::<Count>(ent).unwrap().0, 2);
+
+        world.remove_entity(ent);
+        assert!(world.get_or_insert_component(ent, || Count(0)).is_none());
+    }
+
+    #[test]
+    fn test_add_component_mut() {
+        struct Velocity(f32, f32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        {
+            let mut vel = world.add_component_mut(ent, Velocity(0.0, 0.0)).unwrap();
+            vel.0 = 2.0;
+            vel.1 -= 1.0;
+        }
+
+        let vel = world.get_component_ref::<Velocity>(ent).unwrap();
+        assert_eq!((vel.0, vel.1), (2.0, -1.0));
+        drop(vel);
+
+        world.remove_entity(ent);
+        assert!(world.add_component_mut(ent, Velocity(0.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn test_get_component_or_default() {
+        #[derive(Default)]
+        struct Score(u32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        assert_eq!(world.get_component_or_default::<Score>(ent).unwrap().0, 0);
+
+        world.get_component_or_default::<Score>(ent).unwrap().0 += 5;
+        assert_eq!(world.get_component_or_default::<Score>(ent).unwrap().0, 5);
+
+        world.remove_entity(ent);
+        assert!(world.get_component_or_default::<Score>(ent).is_none());
+    }
+
+    #[test]
+    fn test_swap_components() {
+        struct Item(&'static str);
+        struct A;
+
+        let mut world = World::new();
+        let a = world.create_entity();
+        let b = world.create_entity();
+        let c = world.create_entity();
+        world.add_component(a, Item("sword"));
+        world.add_component(b, Item("shield"));
+        world.add_component(c, A);
+
+        assert!(world.swap_components::<Item>(a, b));
+        assert_eq!(world.get_component::<Item>(a).unwrap().0, "shield");
+        assert_eq!(world.get_component::<Item>(b).unwrap().0, "sword");
+
+        assert_eq!(world.swap_components::<Item>(a, c), false);
+        assert_eq!(world.get_component::<Item>(a).unwrap().0, "shield");
+
+        world.drop_entity(b);
+        assert_eq!(world.swap_components::<Item>(a, b), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "component already borrowed")]
+    fn test_get_component_aliasing() {
+        struct A(u32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A(1));
+
+        let mut first = world.get_component::<A>(ent).unwrap();
+        first.0 += 1;
+        world.get_component::<A>(ent);
+    }
+
+    #[test]
+    fn test_get_component_ref() {
+        struct A(u32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A(7));
+
+        let first = world.get_component_ref::<A>(ent).unwrap();
+        let second = world.get_component_ref::<A>(ent).unwrap();
+        assert_eq!(first.0 + second.0, 14);
+    }
+
+    #[test]
+    #[should_panic(expected = "component already borrowed")]
+    fn test_get_component_while_shared() {
+        struct A(u32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A(7));
+
+        let shared = world.get_component_ref::<A>(ent).unwrap();
+        world.get_component::<A>(ent).unwrap().0 = shared.0 + 1;
+    }
+
+    #[test]
+    fn test_component_borrow_released() {
+        struct A(u32);
+        struct B(u32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A(1));
+        world.add_component(ent, B(2));
+
+        {
+            // Different components of the same entity borrow independently
+            let mut a = world.get_component::<A>(ent).unwrap();
+            let b = world.get_component_ref::<B>(ent).unwrap();
+            let also_b = world.get_component_ref::<B>(ent).unwrap();
+            a.0 += b.0 + also_b.0;
+        }
+
+        world.get_component::<B>(ent).unwrap().0 = 3;
+        assert_eq!(world.get_component_ref::<A>(ent).unwrap().0, 5);
+        assert_eq!(world.get_component_ref::<B>(ent).unwrap().0, 3);
+    }
+
+    #[test]
+    fn test_components_of() {
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A);
+        world.add_component(ent, B);
+
+        let types = world.components_of(ent);
+        assert_eq!(types.len(), 2);
+        assert!(types.contains(&TypeId::of::<A>()));
+        assert!(types.contains(&TypeId::of::<B>()));
+
+        world.drop_entity(ent);
+        assert!(world.components_of(ent).is_empty());
+    }
+
+    #[test]
+    fn test_has_components() {
+        struct A;
+        struct B;
+        struct C;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A);
+        world.add_component(ent, B);
+
+        let (a, b, c) = (TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>());
+        assert!(world.has_components(ent, &[a, b]));
+        assert!(!world.has_components(ent, &[a, b, c]));
+        assert!(world.has_any_component(ent, &[a, b, c]));
+        assert!(!world.has_any_component(ent, &[c]));
+
+        world.drop_entity(ent);
+        assert!(!world.has_components(ent, &[]));
+    }
+
+    #[test]
+    fn test_matches() {
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        let matching = world.create_entity();
+        world.add_component(matching, A);
+        let other = world.create_entity();
+        world.add_component(other, A);
+        world.add_component(other, B);
+
+        let query = Query::new(Matchers::with::<A>().without::<B>());
+        assert!(world.matches(matching, &query));
+        assert!(!world.matches(other, &query));
+
+        world.drop_entity(matching);
+        assert!(!world.matches(matching, &query));
+    }
+
+    #[cfg(feature = "debug-names")]
+    #[test]
+    fn test_type_name_of_components() {
+        struct Position;
+        struct Velocity;
+        struct Opaque;
+
+        let mut world = World::new();
+        let ent = world.spawn().add(Position).add(Velocity).id();
+        world.add_component_dyn(ent, TypeId::of::<Opaque>(), Box::new(Opaque));
+
+        let names = world.type_name_of_components(ent);
+        assert_eq!(names.len(), 3);
+        assert!(names[0].ends_with("Position"));
+        assert!(names[1].ends_with("Velocity"));
+        assert_eq!(names[2], "?");
+
+        world.drop_entity(ent);
+        assert!(world.type_name_of_components(ent).is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        let ents: Vec<Entity> = (0..3).map(|_| world.spawn().add(A).add(B).id()).collect();
+        world.drop_entity(ents[2]);
+        assert_eq!(world.validate(), Ok(()));
+
+        // Bypass `insert`, which would replace the existing `A`
+        let ptr = Box::into_raw(Box::new(A)) as *mut dyn Any;
+        world.entities[ents[1]].borrow_mut().push((TypeId::of::<A>(), ptr));
+        world.free_ents.push_back(ents[0]);
+
+        let errors = world.validate().unwrap_err();
+        assert_eq!(errors, vec!(
+            WorldError::DuplicateComponent(ents[1], TypeId::of::<A>()),
+            WorldError::LiveEntityFreed(ents[0])
+        ));
+        assert_eq!(errors[1].to_string(), "entity 0 is live but has been freed");
+
+        // Put things back so the world can be dropped
+        world.free_ents.pop_back();
+        world.entities[ents[1]].borrow_mut().list.pop();
+        drop(unsafe { Box::from_raw(ptr) });
+        assert_eq!(world.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_collect_matching() {
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        for i in 0..10 {
+            let ent = world.create_entity();
+            world.add_component(ent, A);
+            if i % 3 == 0 {
+                world.add_component(ent, B);
+            }
+        }
+        world.remove_entity(3);
+
+        let matched = world.collect_matching(Matchers::with::<A>().without::<B>());
+        let mut iter = matched.iter();
+        assert_eq!(iter.len(), 6);
+        iter.next();
+        assert_eq!(iter.len(), 5);
+
+        let collected: Vec<Entity> = matched.iter().collect();
+        assert_eq!(collected.len(), matched.len());
+        assert_eq!(collected, vec!(1, 2, 4, 5, 7, 8));
+    }
+
+    #[test]
+    fn test_entities_matching_into() {
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        for i in 0..6 {
+            let ent = world.spawn().add(A).id();
+            if i % 2 == 0 {
+                world.add_component(ent, B);
+            }
+        }
+
+        let mut buffer = Vec::new();
+        world.entities_matching_into(Matchers::with::<A>().without::<B>(), &mut buffer);
+        assert_eq!(buffer, vec!(1, 3, 5));
+
+        let capacity = buffer.capacity();
+        world.drop_entity(3);
+        world.entities_matching_into(Matchers::with::<A>().without::<B>(), &mut buffer);
+        assert_eq!(buffer, vec!(1, 5));
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_iter_entities() {
+        let mut world = World::new();
+        let ents: Vec<Entity> = (0..4).map(|_| world.create_entity()).collect();
+        world.drop_entity(ents[1]);
+
+        assert_eq!(world.iter_entities().collect::<Vec<_>>(), vec!(ents[0], ents[2], ents[3]));
+    }
+
+    #[test]
+    fn test_for_each_component() {
+        struct Cooldown(u32);
+        struct Health;
+
+        let mut world = World::new();
+        for i in 0..8 {
+            let ent = world.create_entity();
+            world.add_component(ent, Cooldown(i));
+            if i % 2 == 0 {
+                world.add_component(ent, Health);
+            }
+        }
+        let dropped = world.create_entity();
+        world.add_component(dropped, Cooldown(100));
+        world.drop_entity(dropped);
+
+        let mut total = 0;
+        world.for_each_component::<Cooldown, _>(|_, cooldown| total += cooldown.0);
+        assert_eq!(total, 28);
+
+        world.for_each_component::<Cooldown, _>(|_, cooldown| cooldown.0 = 0);
+        let mut visited = Vec::new();
+        world.for_each_component::<Cooldown, _>(|ent, cooldown| {
+            assert_eq!(cooldown.0, 0);
+            visited.push(ent);
+        });
+        visited.sort();
+        assert_eq!(visited, world.iter_entities().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_count_matching() {
+        use query::Matchers;
+
+        struct Enemy(u32);
+        struct Dead;
+
+        let mut world = World::new();
+        for i in 0..20 {
+            let ent = world.create_entity();
+            world.add_component(ent, Enemy(i));
+            if i % 4 == 0 {
+                world.add_component(ent, Dead);
+            }
+        }
+        world.remove_entity(1);
+
+        let alive = world.iter_entities()
+            .filter(|&ent| world.has_component::<Enemy>(ent) && !world.has_component::<Dead>(ent))
+            .count();
+        assert_eq!(world.count_matching(Matchers::with::<Enemy>().without::<Dead>()), alive);
+        assert_eq!(alive, 14);
+
+        let strong = world.count_matching(Matchers::where_component(|enemy: &Enemy| enemy.0 >= 10));
+        assert_eq!(strong, 10);
+    }
+
+    #[test]
+    fn test_iter_components() {
+        struct Position(f32, f32);
+        struct Velocity(f32, f32);
+
+        let mut world = World::new();
+        let mut holding = Vec::new();
+        for i in 0..6 {
+            let ent = world.create_entity();
+            if i != 2 {
+                world.add_component(ent, Position(i as f32, 0.0));
+                world.add_component(ent, Velocity(1.0, 1.0));
+                holding.push(ent);
+            }
+        }
+
+        assert_eq!(world.iter_components::<Position>().map(|(ent, _)| ent).collect::<Vec<_>>(), holding);
+
+        for ((_, mut pos), (_, vel)) in world.iter_components::<Position>().zip(world.iter_components::<Velocity>()) {
+            pos.0 += vel.0;
+            pos.1 += vel.1;
+        }
+        let xs: Vec<f32> = world.iter_components::<Position>().map(|(_, pos)| pos.0).collect();
+        assert_eq!(xs, vec!(1.0, 2.0, 4.0, 5.0, 6.0));
+        assert!(world.iter_components::<Position>().all(|(_, pos)| pos.1 == 1.0));
+    }
+
+    #[test]
+    fn test_count_components() {
+        struct Position;
+        struct Velocity;
+        struct Frozen;
+
+        let mut world = World::new();
+        for i in 0..6 {
+            let ent = world.create_entity();
+            world.add_component(ent, Position);
+            if i % 3 == 0 {
+                world.add_component(ent, Velocity);
+            }
+        }
+        let ent = world.create_entity();
+        world.add_component(ent, Velocity);
+
+        assert_eq!(world.count_components::<Position>(), 6);
+        assert_eq!(world.count_components::<Velocity>(), 3);
+        assert_eq!(world.count_components::<Frozen>(), 0);
+
+        world.drop_entity(ent);
+        world.remove_component::<Position>(0);
+        assert_eq!(world.count_components::<Position>(), 5);
+        assert_eq!(world.count_components::<Velocity>(), 2);
+    }
+
+    #[test]
+    fn test_filter_entities() {
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        for i in 0..6 {
+            let ent = world.create_entity();
+            world.add_component(ent, A);
+            if i % 3 == 0 {
+                world.add_component(ent, B);
+            }
+        }
+        world.drop_entity(3);
+
+        assert_eq!(world.filter_entities(Matchers::with::<A>()).collect::<Vec<_>>(), vec!(0, 1, 2, 4, 5));
+        assert_eq!(world.filter_entities(Matchers::with::<B>()).collect::<Vec<_>>(), vec!(0));
+        assert_eq!(world.filter_entities(Matchers::with::<A>().without::<B>()).count(), 4);
+    }
+
+    #[test]
+    fn test_filter_entities_with() {
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        let ents: Vec<Entity> = (0..3).map(|_| world.create_entity()).collect();
+        world.add_component(ents[0], A);
+        world.add_component(ents[1], A);
+        world.add_component(ents[1], B);
+
+        let query = Matchers::with::<A>().without::<B>().build();
+        world.process();
+        assert_eq!(world.filter_entities_with(&query).collect::<Vec<_>>(), vec!(ents[0]));
+
+        world.add_component(ents[2], A);
+        world.remove_component::<B>(ents[1]);
+        world.process();
+        assert_eq!(world.filter_entities_with(&query).collect::<Vec<_>>(), ents);
+    }
+
+    #[test]
+    fn test_get_nth_matching() {
+        use query::Matchers;
+
+        struct Enemy;
+        struct SpawnPoint;
+
+        let mut world = World::new();
+        for i in 0..10 {
+            let ent = world.create_entity();
+            world.add_component(ent, SpawnPoint);
+            if i % 2 == 1 {
+                world.add_component(ent, Enemy);
+            }
+        }
+
+        assert_eq!(world.get_nth_matching(Matchers::with::<Enemy>(), 0), Some(1));
+        assert_eq!(world.get_nth_matching(Matchers::with::<Enemy>(), 2), Some(5));
+        assert_eq!(world.get_nth_matching(Matchers::with::<Enemy>(), 4), Some(9));
+        assert_eq!(world.get_nth_matching(Matchers::with::<Enemy>(), 5), None);
+        assert_eq!(world.get_nth_matching(Matchers::with::<SpawnPoint>(), 9), Some(9));
+    }
+
+    #[test]
+    fn test_component_memory_usage() {
+        #[derive(Default)]
+        #[allow(dead_code)]
+        struct Position(f32, f32);
+        #[allow(dead_code)]
+        struct Health(u64);
+        struct Unknown;
+
+        let mut world = World::new();
+        world.register_named::<Position>("position");
+        world.register_dense_component::<Health>();
+        assert_eq!(world.component_memory_usage(), 0);
+
+        for i in 0..4 {
+            let ent = world.create_entity();
+            world.add_component(ent, Position(0.0, 0.0));
+            if i % 2 == 0 {
+                world.add_component(ent, Health(100));
+            }
+            world.add_component(ent, Unknown);
+        }
+
+        assert_eq!(world.component_memory_usage(), 4 * 8 + 2 * 8);
+        assert_eq!(world.unmeasured_components(), 4);
+
+        world.drop_entity(0);
+        assert_eq!(world.component_memory_usage(), 3 * 8 + 8);
+        assert_eq!(world.unmeasured_components(), 3);
+    }
+
+    #[test]
+    fn test_filter_entities_page() {
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        for i in 0..100 {
+            let ent = world.create_entity();
+            world.add_component(ent, A);
+            if i % 2 == 0 {
+                world.add_component(ent, B);
+            }
+        }
+
+        let mut pages = Vec::new();
+        for page in 0..11 {
+            let ents: Vec<Entity> = world.filter_entities_page(Matchers::with::<A>(), page * 10, 10).collect();
+            pages.push(ents);
+        }
+
+        assert_eq!(pages[0], (0..10).collect::<Vec<_>>());
+        assert_eq!(pages[9], (90..100).collect::<Vec<_>>());
+        assert!(pages[10].is_empty());
+
+        let evens: Vec<Entity> = world.filter_entities_page(Matchers::with::<B>(), 45, 10).collect();
+        assert_eq!(evens, vec!(90, 92, 94, 96, 98));
+    }
+
+    #[test]
+    fn test_merge() {
+        use query::Matchers;
+
+        struct A(u32);
+        struct B;
+
+        let mut world = World::new();
+        for i in 0..3 {
+            let ent = world.create_entity();
+            world.add_component(ent, A(i));
+        }
+
+        let mut chunk = World::new();
+        let mut chunk_ents = Vec::new();
+        for i in 0..4 {
+            let ent = chunk.create_entity();
+            chunk.add_component(ent, A(10 + i));
+            chunk.add_component(ent, B);
+            chunk_ents.push(ent);
+        }
+        chunk.drop_entity(chunk_ents[1]);
+        chunk.remove_entity(chunk_ents[2]);
+
+        let ids = world.merge(chunk);
+        assert_eq!(ids.len(), 2);
+        assert_eq!(world.count_matching(Matchers::with::<A>()), 5);
+        assert_eq!(world.count_matching(Matchers::with::<B>()), 2);
+
+        let moved = ids[&chunk_ents[3]];
+        assert_eq!(world.get_component_ref::<A>(moved).unwrap().0, 13);
+        assert!(world.has_component::<B>(moved));
+
+        // Links between merged entities follow them to their new IDs
+        let mut chunk = World::new();
+        let parent = chunk.create_entity();
+        let child = chunk.create_entity();
+        assert!(chunk.set_parent(child, parent));
+
+        let ids = world.merge(chunk);
+        let (parent, child) = (ids[&parent], ids[&child]);
+        assert_eq!(*world.get_component_ref::<Parent>(child).unwrap(), Parent(parent));
+        assert_eq!(*world.get_component_ref::<Children>(parent).unwrap(), Children(vec!(child)));
+        assert!(world.get_component_ref::<Children>(0).is_none());
+    }
+
+    #[test]
+    fn test_move_entity_to() {
+        use std::rc::Rc;
+
+        #[derive(Debug, PartialEq)]
+        struct Projectile(f32, f32);
+
+        let drops = Rc::new(());
+        let mut src = World::new();
+        let mut dst = World::new();
+        src.register_dense_component::<Projectile>();
+        dst.spawn();
+
+        let ent = src.spawn().add(Projectile(3.0, -1.0)).add(drops.clone()).id();
+        let moved = src.move_entity_to(ent, &mut dst).unwrap();
+        assert_eq!(moved, 1);
+
+        assert!(src.get_component_ref::<Projectile>(ent).is_none());
+        assert_eq!(src.count_components::<Projectile>(), 0);
+        assert_eq!(*dst.get_component_ref::<Projectile>(moved).unwrap(), Projectile(3.0, -1.0));
+        assert!(dst.has_component::<Rc<()>>(moved));
+        assert_eq!(src.move_entity_to(ent, &mut dst), None);
+
+        // Each component is freed exactly once, by the world it ended up in
+        src.clear();
+        assert_eq!(Rc::strong_count(&drops), 2);
+        assert_eq!(dst.validate(), Ok(()));
+        dst.clear();
+        assert_eq!(Rc::strong_count(&drops), 1);
+    }
+
+    #[test]
+    fn test_move_parented_entity() {
+        struct Position(f32, f32);
+
+        let mut src = World::new();
+        let mut dst = World::new();
+        src.add_spatial_grid(4.0, |pos: &Position| (pos.0, pos.1));
+        let unrelated = dst.create_entity();
+
+        let parent = src.create_entity();
+        let ent = src.create_entity();
+        let child = src.create_entity();
+        src.add_component(ent, Position(1.0, 1.0));
+        assert!(src.set_parent(ent, parent));
+        assert!(src.set_parent(child, ent));
+        src.process();
+        assert_eq!(src.query_radius((0.0, 0.0), 2.0), vec!(ent));
+
+        let moved = src.move_entity_to(ent, &mut dst).unwrap();
+        assert_eq!(*src.get_component_ref::<Children>(parent).unwrap(), Children(vec!()));
+        assert!(src.get_component_ref::<Parent>(child).is_none());
+        assert!(dst.get_component_ref::<Parent>(moved).is_none());
+        assert!(dst.get_component_ref::<Children>(moved).is_none());
+        assert!(src.get_resource::<SpatialGrid>().unwrap().is_empty());
+
+        // The reused ID isn't taken for the parent's child
+        let reused = src.create_entity();
+        assert_eq!(reused, ent);
+        src.despawn_recursive(parent);
+        assert!(src.is_valid(reused));
+        assert!(dst.is_valid(unrelated));
+    }
+
+    #[test]
+    fn test_get_two_mut() {
+        struct Position(f32, f32);
+        struct Velocity(f32, f32);
+        struct Mass(f32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, Position(0.0, 0.0));
+        world.add_component(ent, Velocity(1.0, 2.0));
+
+        {
+            let (mut pos, mut vel) = world.get_two_mut::<Position, Velocity>(ent).unwrap();
+            pos.0 += vel.0;
+            pos.1 += vel.1;
+            vel.0 = 0.0;
+        }
+
+        assert_eq!(world.get_component_ref::<Position>(ent).unwrap().1, 2.0);
+        assert_eq!(world.get_component_ref::<Velocity>(ent).unwrap().0, 0.0);
+        assert!(world.get_two_mut::<Position, Position>(ent).is_none());
+        assert!(world.get_two_mut::<Position, Mass>(ent).is_none());
+
+        world.add_component(ent, Mass(2.0));
+        let (_, vel, mut mass) = world.get_three_mut::<Position, Velocity, Mass>(ent).unwrap();
+        mass.0 *= vel.1;
+        assert_eq!(mass.0, 4.0);
+        assert!(world.get_three_mut::<Position, Velocity, Velocity>(ent).is_none());
+    }
+
+    #[test]
+    fn test_clear() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A(Rc<Cell<usize>>);
+
+        impl Drop for A {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        struct Counter(Rc<Cell<usize>>);
+
+        impl IterativeSystem for Counter {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let dropped = Rc::new(Cell::new(0));
+        let counted = Rc::new(Cell::new(0));
+        let mut world = World::new();
+        world.register_iterative_system(Counter(counted.clone()));
+
+        for _ in 0..4 {
+            let ent = world.create_entity();
+            world.add_component(ent, A(dropped.clone()));
+        }
+        world.drop_entity(1);
+        world.remove_entity(2);
+        world.process();
+        assert_eq!(counted.get(), 2);
+
+        world.clear();
+        assert_eq!(dropped.get(), 4);
+        assert_eq!(world.entities.len(), 0);
+        assert_eq!(world.iter_entities().count(), 0);
+
+        let ent = world.create_entity();
+        assert_eq!(ent, 0);
+        world.add_component(ent, A(dropped.clone()));
+        world.process();
+        assert_eq!(counted.get(), 3);
+    }
+
+    #[test]
+    fn test_changed_filter() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Position(u32);
+
+        struct Mover;
+        struct Watcher(Rc<RefCell<Vec<Entity>>>);
+
+        impl IterativeSystem for Mover {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Position>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                let even = world.get_component_ref::<Position>(ent).unwrap().0.is_multiple_of(2);
+                if even {
+                    world.get_component::<Position>(ent).unwrap().0 += 2;
+                }
+            }
+        }
+
+        impl IterativeSystem for Watcher {
+            fn get_query() -> Query {
+                Query::new(Matchers::changed::<Position>())
+            }
+
+            fn process(&mut self, ent: Entity, _world: &World) {
+                self.0.borrow_mut().push(ent);
+            }
+        }
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::new();
+        world.register_iterative_system(Mover);
+        world.register_iterative_system(Watcher(seen.clone()));
+
+        for i in 0..4 {
+            let ent = world.create_entity();
+            world.add_component(ent, Position(i));
+        }
+
+        // Every component is new on the first tick
+        world.process();
+        assert_eq!(*seen.borrow(), vec!(0, 1, 2, 3));
+
+        seen.borrow_mut().clear();
+        world.process();
+        assert_eq!(*seen.borrow(), vec!(0, 2));
+    }
+
+    #[test]
+    fn test_changed_cleared_at_end_of_tick() {
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        let ents: Vec<Entity> = (0..4).map(|_| world.create_entity()).collect();
+        for &ent in ents.iter() {
+            world.add_component(ent, A);
+            world.add_component(ent, B);
+        }
+        assert_eq!(*world.changed_ents.get_mut(), ents);
+
+        world.process();
+        assert!(world.changed_ents.get_mut().is_empty());
+        assert!(!world.entities[ents[0]].borrow().changed(TypeId::of::<A>()));
+
+        // Borrowing twice lists the entity once
+        drop(world.get_component::<A>(ents[1]));
+        drop(world.get_component::<B>(ents[1]));
+        world.swap_components::<A>(ents[2], ents[3]);
+        assert_eq!(*world.changed_ents.get_mut(), vec!(ents[1], ents[2], ents[3]));
+        assert!(world.entities[ents[1]].borrow().changed(TypeId::of::<B>()));
+
+        world.process();
+        assert!(world.changed_ents.get_mut().is_empty());
+        assert!(!world.entities[ents[1]].borrow().changed(TypeId::of::<B>()));
+    }
+
+    #[test]
+    fn test_added_removed_filters() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        struct Added(Rc<RefCell<Vec<Entity>>>);
+        struct Removed(Rc<RefCell<Vec<Entity>>>);
+        struct Swapper;
+
+        impl IterativeSystem for Added {
+            fn get_query() -> Query {
+                Query::new(Matchers::added::<A>())
+            }
+
+            fn process(&mut self, ent: Entity, _world: &World) {
+                self.0.borrow_mut().push(ent);
+            }
+        }
+
+        impl IterativeSystem for Removed {
+            fn get_query() -> Query {
+                Query::new(Matchers::removed::<A>())
+            }
+
+            fn process(&mut self, ent: Entity, _world: &World) {
+                self.0.borrow_mut().push(ent);
+            }
+        }
+
+        // Moves `A` from entities with `B` to the next entity along, once
+        impl IterativeSystem for Swapper {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>().with::<B>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                assert!(world.remove_component::<A>(ent).is_some());
+                world.remove_component::<B>(ent);
+                world.add_component(ent + 1, A);
+            }
+        }
+
+        let added = Rc::new(RefCell::new(Vec::new()));
+        let removed = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::new();
+        world.register_iterative_system(Added(added.clone()));
+        world.register_iterative_system(Removed(removed.clone()));
+        world.register_iterative_system(Swapper);
+
+        for _ in 0..3 {
+            world.create_entity();
+        }
+        world.add_component(0, A);
+        world.add_component(1, A);
+        world.add_component(1, B);
+
+        world.process();
+        assert_eq!(*added.borrow(), vec!(0, 1));
+        assert!(removed.borrow().is_empty());
+
+        added.borrow_mut().clear();
+        world.process();
+        assert_eq!(*added.borrow(), vec!(2));
+        assert_eq!(*removed.borrow(), vec!(1));
+
+        added.borrow_mut().clear();
+        removed.borrow_mut().clear();
+        world.process();
+        assert!(added.borrow().is_empty());
+        assert!(removed.borrow().is_empty());
+        assert!(world.evented_ents.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_dyn_components() {
+        struct Health(u32);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        let ty = TypeId::of::<Health>();
+
+        assert!(world.add_component_dyn(ent, ty, Box::new(Health(10))));
+        assert_eq!(world.get_component_ref::<Health>(ent).unwrap().0, 10);
+
+        let ptr = world.get_component_dyn(ent, ty).unwrap();
+        let health = unsafe { (*ptr).downcast_mut::<Health>().unwrap() };
+        health.0 += 5;
+
+        let removed = world.remove_component_dyn(ent, ty).unwrap();
+        assert_eq!(removed.downcast::<Health>().unwrap().0, 15);
+        assert!(world.get_component_dyn(ent, ty).is_none());
+        assert!(!world.has_component::<Health>(ent));
+
+        // Dense storage works the same way
+        world.register_dense_component::<Health>();
+        assert!(world.add_component_dyn(ent, ty, Box::new(Health(1))));
+        assert_eq!(world.remove_component::<Health>(ent).map(|h| h.0), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "component isn't of the given type")]
+    fn test_add_component_dyn_mismatch() {
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component_dyn(ent, TypeId::of::<u32>(), Box::new(1u64));
+    }
+
+    #[test]
+    fn test_remove_component() {
+        struct A(u32);
+        struct B;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A(3));
+        world.add_component(ent, B);
+
+        assert_eq!(world.remove_component::<A>(ent).map(|a| a.0), Some(3));
+        assert!(world.remove_component::<A>(ent).is_none());
+        assert!(!world.has_component::<A>(ent));
+        assert!(world.has_component::<B>(ent));
+        assert_eq!(world.components_of(ent), vec!(TypeId::of::<B>()));
+    }
+
+    #[test]
+    fn test_remove_component_while_earlier_borrowed() {
+        struct A(u32);
+        struct B;
+        struct C;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A(1));
+        world.add_component(ent, B);
+        world.add_component(ent, C);
+
+        // Components before the one removed keep their place, so their guards
+        // stay valid
+        let mut a = world.get_component::<A>(ent).unwrap();
+        assert!(world.remove_component::<C>(ent).is_some());
+        a.0 = 2;
+        drop(a);
+
+        assert_eq!(world.get_component_ref::<A>(ent).unwrap().0, 2);
+        assert!(world.get_component::<B>(ent).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "later component of the entity is borrowed")]
+    fn test_remove_component_while_later_borrowed() {
+        struct A;
+        struct B;
+        struct C;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, A);
+        world.add_component(ent, B);
+        world.add_component(ent, C);
+
+        let _c = world.get_component::<C>(ent).unwrap();
+        world.remove_component::<A>(ent);
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut world = World::with_capacity(0);
+        world.reserve(1000);
+        assert!(world.capacity() >= 1000);
+        assert_eq!(world.iter_entities().count(), 0);
+
+        for _ in 0..10 {
+            world.create_entity();
+        }
+        world.shrink_to_fit();
+        assert!(world.capacity() >= 10 && world.capacity() < 1000);
+        assert_eq!(world.iter_entities().count(), 10);
+    }
+
+    #[test]
+    fn test_defragment() {
+        use query::Matchers;
+
+        struct Id(usize);
+        struct Tag;
+
+        let mut world = World::with_capacity(64);
+        let ents: Vec<Entity> = (0..20).map(|i| world.spawn().add(Id(i)).id()).collect();
+        let parent = ents[12];
+        // Dropped below, which takes it out of `Children`
+        assert!(world.set_parent(ents[4], parent));
+        for &ent in ents.iter().filter(|&&ent| ent % 3 != 0) {
+            world.drop_entity(ent);
+        }
+        world.add_component(ents[9], Tag);
+        world.remove_entity(ents[18]);
+        assert!(world.set_parent(ents[15], parent));
+        world.process();
+
+        let moved = world.defragment();
+        assert_eq!(moved.len(), 6);
+        assert_eq!(world.iter_entities().collect::<Vec<_>>(), (0..6).collect::<Vec<_>>());
+        assert!(world.capacity() >= 6 && world.capacity() < 64);
+        for (&old, &new) in moved.iter() {
+            assert_eq!(world.get_component_ref::<Id>(new).unwrap().0, old);
+        }
+
+        assert_eq!(world.collect_matching(Matchers::with::<Tag>()).iter().collect::<Vec<_>>(), vec!(moved[&ents[9]]));
+        assert_eq!(world.get_component_ref::<Parent>(moved[&ents[15]]).unwrap().0, moved[&parent]);
+        assert_eq!(world.get_component_ref::<Children>(moved[&parent]).unwrap().0, vec!(moved[&ents[15]]));
+        assert!(world.validate().is_ok());
+
+        assert_eq!(world.create_entity(), 6);
+    }
+
+    #[test]
+    fn test_growth_policy() {
+        let capacities = |growth| {
+            let mut world = World::builder().initial_capacity(100).growth(growth).build();
+            let mut capacities = vec!(world.capacity());
+            for _ in 0..250 {
+                world.create_entity();
+                if world.capacity() != *capacities.last().unwrap() {
+                    capacities.push(world.capacity());
+                }
+            }
+
+            capacities
+        };
+
+        assert_eq!(capacities(GrowthPolicy::Fixed(100)), vec!(100, 200, 300));
+        assert_eq!(capacities(GrowthPolicy::Double), vec!(100, 200, 400));
+        assert_eq!(capacities(GrowthPolicy::Exact), (100..=250).collect::<Vec<_>>());
+
+        let mut world = World::builder().initial_capacity(0).growth(GrowthPolicy::Fixed(0)).build();
+        world.create_entity();
+        assert_eq!(world.capacity(), 1);
+    }
+
+    #[test]
+    fn test_reuse_policy() {
+        let reused = |reuse| {
+            let mut world = World::builder().initial_capacity(4).reuse(reuse).build();
+            let ents: Vec<Entity> = (0..3).map(|_| world.create_entity()).collect();
+            for &ent in ents.iter() {
+                world.drop_entity(ent);
+            }
+
+            world.create_entity()
+        };
+
+        assert_eq!(reused(ReusePolicy::Fifo), 0);
+        assert_eq!(reused(ReusePolicy::Lifo), 2);
+    }
+
+    #[test]
+    fn test_debug() {
+        struct A;
+
+        let mut world = World::new();
+        let first = world.create_entity();
+        let removed = world.create_entity();
+        world.create_entity();
+        world.remove_entity(removed);
+
+        assert_eq!(
+            format!("{:?}", world),
+            "World { live_entities: 2, free_entities: 0, dead_entities: 1, systems: 0, entities: {0: [], 2: []} }"
+        );
+
+        world.add_component(first, A);
+        assert!(format!("{:?}", world).contains(&format!("0: [{:?}]", TypeId::of::<A>())));
+
+        for _ in 0..DEBUG_ENTITIES {
+            world.create_entity();
+        }
+        assert!(format!("{:?}", world).ends_with(", ..} }"));
+    }
+
+    #[test]
+    fn test_dense_component() {
+        use std::rc::Rc;
+
+        #[allow(dead_code)]
+        struct Position(u32, Rc<()>);
+
+        let alive = Rc::new(());
+        let mut world = World::new();
+        let boxed = world.create_entity();
+        world.add_component(boxed, Position(1, alive.clone()));
+
+        // Existing components move into the column
+        world.register_dense_component::<Position>();
+        assert_eq!(world.get_component_ref::<Position>(boxed).unwrap().0, 1);
+
+        let ents: Vec<Entity> = (0..3000).map(|i| {
+            let ent = world.create_entity();
+            world.add_component(ent, Position(i, alive.clone()));
+            ent
+        }).collect();
+        let first = world.get_component_ref::<Position>(ents[0]).unwrap();
+        for ent in ents.iter().skip(1).step_by(2) {
+            world.remove_entity(*ent);
+        }
+        world.add_component(ents[2], Position(20, alive.clone()));
+        assert_eq!(first.0, 0);
+        drop(first);
+
+        world.get_component::<Position>(ents[4]).unwrap().0 += 1;
+        assert_eq!(world.get_component_ref::<Position>(ents[4]).unwrap().0, 5);
+        assert_eq!(world.remove_component::<Position>(ents[2]).map(|p| p.0), Some(20));
+
+        let mut other = World::new();
+        let moved = world.create_entity();
+        world.add_component(moved, Position(7, alive.clone()));
+        let ids = other.merge(world);
+        assert_eq!(other.get_component_ref::<Position>(ids[&moved]).unwrap().0, 7);
+        assert_eq!(other.get_component_ref::<Position>(ids[&ents[4]]).unwrap().0, 5);
+
+        other.clear();
+        assert_eq!(Rc::strong_count(&alive), 1);
+    }
+
+    #[test]
+    fn test_reuse_entity() {
+        struct A;
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.drop_entity(ent);
+
+        let ent = world.create_entity();
+        assert_eq!(world.entities.len(), 1);
+        assert!(world.add_component(ent, A));
+        assert!(world.has_component::<A>(ent));
+    }
+
+    #[test]
+    fn test_archetype_grouping() {
+        struct A;
+        struct B;
+
+        let mut world = World::new();
+        let a = world.create_entity();
+        world.add_component(a, A);
+        let b = world.create_entity();
+        world.add_component(b, A);
+        let ab = world.create_entity();
+        world.add_component(ab, A);
+        world.add_component(ab, B);
+
+        let archetypes = world.archetypes.borrow();
+        // The empty archetype, {A} and {A, B}
+        assert_eq!(archetypes.len(), 3);
+        assert_eq!(archetypes.get(0).entities.len(), 0);
+        assert_eq!(archetypes.get(1).entities, vec!(a, b));
+        assert_eq!(archetypes.get(2).entities, vec!(ab));
+    }
+
+    #[test]
+    fn test_cache_refreshes_after_structural_change() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A;
+        struct Tag;
+
+        struct Tagger;
+        struct TagCounter(Rc<Cell<usize>>);
+
+        impl IterativeSystem for Tagger {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>().without::<Tag>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                world.add_component(ent, Tag);
+            }
+        }
+
+        impl IterativeSystem for TagCounter {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Tag>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let counted = Rc::new(Cell::new(0));
+        let mut world = World::new();
+        world.register_iterative_system(TagCounter(counted.clone()));
+        world.register_iterative_system(Tagger);
+
+        for _ in 0..3 {
+            let ent = world.create_entity();
+            world.add_component(ent, A);
+        }
+
+        // Tags are added after `TagCounter` has already run this tick
+        world.process();
+        assert_eq!(counted.get(), 0);
+
+        world.process();
+        assert_eq!(counted.get(), 3);
+
+        let version = world.structure_version.get();
+        world.process();
+        assert_eq!(counted.get(), 6);
+        assert_eq!(world.structure_version.get(), version);
+    }
+
+    #[test]
+    fn test_apply() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A;
+
+        struct Counter(Rc<Cell<usize>>);
+
+        impl IterativeSystem for Counter {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let counted = Rc::new(Cell::new(0));
+        let mut world = World::new();
+        world.register_iterative_system(Counter(counted.clone()));
+
+        let version = world.structure_version.get();
+        world.apply(|world| {
+            for _ in 0..100 {
+                let ent = world.create_entity();
+                world.add_component(ent, A);
+            }
+        });
+
+        // The batch counts as a single change, and the cache was rebuilt for it
+        assert_eq!(world.structure_version.get(), version + 1);
+        assert_eq!(world.systems[0].matches.borrow().version, Some(version + 1));
+        assert_eq!(world.systems[0].matches.borrow().entities.len(), 100);
+
+        world.process();
+        assert_eq!(counted.get(), 100);
+        assert_eq!(world.structure_version.get(), version + 1);
+
+        // Systems run inside a batch still see the changes made before them
+        world.apply(|world| {
+            let ent = world.create_entity();
+            world.add_component(ent, A);
+            world.process();
+        });
+        assert_eq!(counted.get(), 201);
+        assert_eq!(world.structure_version.get(), version + 2);
+
+        // A panicking batch still ends
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            world.apply(|world| {
+                world.create_entity();
+                panic!("batch failed");
+            });
+        }));
+        assert!(result.is_err());
+        assert_eq!(world.batch_depth, 0);
+        world.create_entity();
+        assert_eq!(world.current_version(), version + 4);
+    }
+
+    #[test]
+    fn test_indexed_components() {
+        struct A;
+        struct Marker<T>(T);
+
+        let mut world = World::new();
+        let ent = world.create_entity();
+        world.add_component(ent, Marker(0u8));
+        world.add_component(ent, Marker(1u16));
+        world.add_component(ent, Marker(2u32));
+        world.add_component(ent, Marker(3u64));
+        world.add_component(ent, Marker(4i8));
+        world.add_component(ent, Marker(5i16));
+        world.add_component(ent, Marker(6i32));
+        world.add_component(ent, Marker(7i64));
+        assert!(world.entities[ent].borrow().index.is_none());
+
+        world.add_component(ent, Marker(8usize));
+        assert!(world.entities[ent].borrow().index.is_some());
+
+        assert_eq!(world.get_component::<Marker<u8>>(ent).unwrap().0, 0);
+        assert_eq!(world.get_component::<Marker<i64>>(ent).unwrap().0, 7);
+        assert_eq!(world.get_component::<Marker<usize>>(ent).unwrap().0, 8);
+        assert!(!world.has_component::<A>(ent));
+
+        world.add_component(ent, A);
+        assert!(world.has_component::<A>(ent));
+    }
+
+    #[test]
+    fn test_value_predicate_system() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Health(u32);
+
+        struct LowHealth(Rc<RefCell<Vec<Entity>>>);
+
+        impl IterativeSystem for LowHealth {
+            fn get_query() -> Query {
+                Query::new(Matchers::where_component(|health: &Health| health.0 < 10))
+            }
+
+            fn process(&mut self, ent: Entity, _world: &World) {
+                self.0.borrow_mut().push(ent);
+            }
+        }
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
         let mut world = World::new();
+        world.register_iterative_system(LowHealth(seen.clone()));
+
+        let healthy = world.create_entity();
+        world.add_component(healthy, Health(100));
+        let hurt = world.create_entity();
+        world.add_component(hurt, Health(5));
         world.create_entity();
 
-        assert_eq!(world.entities.len(), 1);
+        world.process();
+        assert_eq!(*seen.borrow(), vec!(hurt));
+
+        // Values change without touching the world's structure
+        world.get_component::<Health>(healthy).unwrap().0 = 1;
+        world.get_component::<Health>(hurt).unwrap().0 = 50;
+        seen.borrow_mut().clear();
+
+        world.process();
+        assert_eq!(*seen.borrow(), vec!(healthy));
     }
-    
+
     #[test]
-    fn test_drop_entity() {
+    fn test_get_system_mut() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Counter(u32);
+
+        struct Step {
+            amount: u32
+        }
+
+        struct Other;
+
+        impl IterativeSystem for Step {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Counter>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                world.get_component::<Counter>(ent).unwrap().0 += self.amount;
+            }
+        }
+
+        impl IterativeSystem for Other {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Counter>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {}
+        }
+
         let mut world = World::new();
+        world.register_iterative_system(Other);
+        world.register_iterative_system(Step { amount: 1 });
         let ent = world.create_entity();
+        world.add_component(ent, Counter(0));
 
-        assert_eq!(world.entities.len(), 1);
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 1);
 
-        world.drop_entity(ent);
-        assert_eq!(world.entities.len(), 1);
-        assert_eq!(world.valid_ents[ent], false);
+        world.get_system_mut::<Step>().unwrap().amount = 10;
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 11);
+
+        assert!(world.get_system_mut::<Other>().is_some());
+        assert_eq!(world.system_count(), 2);
+        assert!(world.is_system_registered::<Step>());
+        assert!(!world.is_system_registered::<Counter>());
+        assert!(World::new().get_system_mut::<Step>().is_none());
     }
 
     #[test]
-    fn test_remove_entity() {
+    fn test_run_criteria() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Counter(u32);
+        struct Paused(bool);
+
+        struct Step;
+
+        impl IterativeSystem for Step {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Counter>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                world.get_component::<Counter>(ent).unwrap().0 += 1;
+            }
+        }
+
         let mut world = World::new();
+        world.insert_resource(Paused(false));
+        world.register_iterative_system_if(Step, |world: &World| !world.get_resource::<Paused>().unwrap().0);
         let ent = world.create_entity();
+        world.add_component(ent, Counter(0));
 
-        assert_eq!(world.entities.len(), 1);
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 1);
 
-        world.remove_entity(ent);
+        world.get_resource_mut::<Paused>().unwrap().0 = true;
         world.process();
-        assert_eq!(world.entities.len(), 1);
-        assert_eq!(world.valid_ents[ent], false);
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 1);
+
+        world.get_resource_mut::<Paused>().unwrap().0 = false;
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 2);
+    }
+
+    #[test]
+    fn test_set_system_enabled() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Counter(u32);
+
+        struct Step {
+            runs: u32
+        }
+
+        impl IterativeSystem for Step {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Counter>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                self.runs += 1;
+                world.get_component::<Counter>(ent).unwrap().0 = self.runs;
+            }
+        }
+
+        let mut world = World::new();
+        let handle = world.register_iterative_system(Step { runs: 0 });
+        let ent = world.create_entity();
+        world.add_component(ent, Counter(0));
+
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 1);
+
+        assert!(world.set_system_enabled(handle, false));
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 1);
+
+        // The system's own state carries over
+        assert!(world.set_system_enabled(handle, true));
+        world.process();
+        assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 2);
+
+        // Handles from other worlds are refused rather than indexed
+        let mut other = World::new();
+        assert!(!other.set_system_enabled(handle, false));
+        assert!(other.system_name(handle).is_none());
+        assert!(!other.unregister_system(handle));
+    }
+
+    #[test]
+    fn test_system_lifecycle() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Gravity(f32);
+        struct Velocity(f32);
+
+        struct Fall;
+
+        impl IterativeSystem for Fall {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Velocity>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                let gravity = world.get_resource::<Gravity>().unwrap().0;
+                world.get_component::<Velocity>(ent).unwrap().0 -= gravity;
+            }
+
+            fn on_register(&mut self, world: &mut World) {
+                world.insert_resource(Gravity(2.0));
+            }
+
+            fn on_unregister(&mut self, world: &mut World) {
+                world.remove_resource::<Gravity>();
+            }
+        }
+
+        let mut world = World::new();
+        let handle = world.register_iterative_system(Fall);
+        assert!(world.get_resource::<Gravity>().is_some());
+
+        let ent = world.create_entity();
+        world.add_component(ent, Velocity(0.0));
+        world.process();
+        assert_eq!(world.get_component_ref::<Velocity>(ent).unwrap().0, -2.0);
+
+        assert!(world.unregister_system(handle));
+        assert!(world.get_resource::<Gravity>().is_none());
+        assert!(!world.unregister_system(handle));
+        assert!(!world.is_system_registered::<Fall>());
+
+        assert!(!world.set_system_enabled(handle, true));
+        world.process();
+        assert_eq!(world.get_component_ref::<Velocity>(ent).unwrap().0, -2.0);
+        assert!(format!("{:?}", world).contains("systems: 0"));
+    }
+
+    #[test]
+    fn test_stages() {
+        use std::rc::Rc;
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A;
+
+        struct Log(&'static str, Rc<RefCell<Vec<&'static str>>>);
+
+        impl IterativeSystem for Log {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {
+                self.1.borrow_mut().push(self.0);
+            }
+        }
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut world = World::new();
+        world.add_stage("update");
+        world.add_stage("render");
+
+        // Registered in the opposite order to the stages
+        world.register_iterative_system_in("render", Log("draw", log.clone()));
+        world.register_iterative_system_in("update", Log("move", log.clone()));
+        world.register_iterative_system_in("render", Log("present", log.clone()));
+        let handle = world.register_iterative_system(Log("input", log.clone()));
+        world.register_iterative_system_in("update", Log("collide", log.clone()));
+
+        let ent = world.create_entity();
+        world.add_component(ent, A);
+
+        let stats = world.tick();
+        assert_eq!(*log.borrow(), vec!("input", "move", "collide", "draw", "present"));
+        assert_eq!(stats.per_system[0].0, handle);
+
+        // Re-adding a stage doesn't move it
+        world.add_stage("update");
+        log.borrow_mut().clear();
+        world.process();
+        assert_eq!(*log.borrow(), vec!("input", "move", "collide", "draw", "present"));
+    }
+
+    #[test]
+    #[should_panic(expected = "no stage named")]
+    fn test_unknown_stage() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Noop;
+
+        impl IterativeSystem for Noop {
+            fn get_query() -> Query {
+                Query::new(Matchers::any())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {}
+        }
+
+        let mut world = World::new();
+        world.register_iterative_system_in("physics", Noop);
+    }
+
+    #[test]
+    fn test_tick_stats() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A;
+        struct B;
+
+        struct WithA;
+        struct WithB;
+
+        impl IterativeSystem for WithA {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                if world.has_component::<B>(ent) {
+                    world.remove_entity(ent);
+                }
+            }
+        }
+
+        impl IterativeSystem for WithB {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<B>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {}
+        }
+
+        let mut world = World::new();
+        let with_a = world.register_iterative_system(WithA);
+        let with_b = world.register_iterative_system(WithB);
+        let skipped = world.register_iterative_system_if(WithB, |_: &World| false);
+
+        for i in 0..4 {
+            let ent = world.create_entity();
+            world.add_component(ent, A);
+            if i % 2 == 1 {
+                world.add_component(ent, B);
+            }
+        }
+
+        let stats = world.tick();
+        assert_eq!(stats.systems_run, 2);
+        // Entities removed by `WithA` aren't visited by `WithB`
+        assert_eq!(stats.entities_visited, 4);
+        assert_eq!(stats.entities_despawned, 2);
+        assert_eq!(stats.per_system.iter().map(|&(handle, _, visited)| (handle, visited)).collect::<Vec<_>>(), vec!((with_a, 4), (with_b, 0)));
+        assert!(stats.per_system.iter().all(|&(handle, _, _)| handle != skipped));
+
+        let stats = world.tick();
+        assert_eq!(stats.entities_visited, 2);
+        assert_eq!(stats.entities_despawned, 0);
+    }
+
+    #[test]
+    fn test_system_name() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct A;
+        struct Unnamed;
+        struct Named;
+
+        impl IterativeSystem for Unnamed {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {}
+        }
+
+        impl IterativeSystem for Named {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<A>())
+            }
+
+            fn process(&mut self, _ent: Entity, _world: &World) {}
+
+            fn name(&self) -> &'static str {
+                "named"
+            }
+        }
+
+        let mut world = World::new();
+        let unnamed = world.register_iterative_system(Unnamed);
+        let named = world.register_iterative_system(Named);
+        assert!(world.system_name(unnamed).unwrap().ends_with("Unnamed"));
+        assert_eq!(world.system_name(named), Some("named"));
+
+        let stats = world.tick();
+        assert_eq!(stats.per_system[1], (named, "named", 0));
+
+        world.unregister_system(named);
+        assert_eq!(world.system_name(named), None);
+    }
+
+    #[test]
+    fn test_foreign_system_handle() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Count(u32);
+        struct CountSystem;
+
+        impl IterativeSystem for CountSystem {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Count>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                world.get_component::<Count>(ent).unwrap().0 += 1;
+            }
+        }
+
+        let mut world = World::new();
+        world.register_iterative_system(CountSystem);
+        let ent = world.create_entity();
+        world.add_component(ent, Count(0));
+
+        // Registered first in both worlds, so the handles share an index
+        let mut other = World::new();
+        let foreign = other.register_iterative_system(CountSystem);
+
+        assert_eq!(world.system_name(foreign), None);
+        assert!(!world.process_system(foreign));
+        assert!(!world.set_system_enabled(foreign, false));
+        assert!(!world.unregister_system(foreign));
+        assert_eq!(world.system_count(), 1);
+
+        world.process();
+        assert_eq!(world.get_component_ref::<Count>(ent).unwrap().0, 1);
+        assert!(other.system_name(foreign).is_some());
+    }
+
+    #[test]
+    fn test_process_system() {
+        use systems::IterativeSystem;
+        use query::Matchers;
+
+        struct Value(u32);
+
+        struct Double;
+        struct Increment;
+
+        impl IterativeSystem for Double {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Value>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                world.get_component::<Value>(ent).unwrap().0 *= 2;
+            }
+        }
+
+        impl IterativeSystem for Increment {
+            fn get_query() -> Query {
+                Query::new(Matchers::with::<Value>())
+            }
+
+            fn process(&mut self, ent: Entity, world: &World) {
+                world.get_component::<Value>(ent).unwrap().0 += 1;
+                if world.get_component_ref::<Value>(ent).unwrap().0 > 10 {
+                    world.remove_entity(ent);
+                }
+            }
+        }
+
+        let mut world = World::new();
+        let double = world.register_iterative_system(Double);
+        let increment = world.register_iterative_system(Increment);
+        let ent = world.create_entity();
+        world.add_component(ent, Value(1));
+
+        // Reverse of registration order
+        assert!(world.process_system(increment));
+        assert!(world.process_system(double));
+        assert_eq!(world.get_component_ref::<Value>(ent).unwrap().0, 4);
+
+        world.process_system(double);
+        world.process_system(increment);
+        assert_eq!(world.get_component_ref::<Value>(ent).unwrap().0, 9);
+
+        world.process_system(double);
+        world.process_system(increment);
+        assert!(!world.is_valid(ent));
+
+        world.set_system_enabled(double, false);
+        assert!(!world.process_system(double));
+        assert!(!World::new().process_system(double));
+    }
+
+    #[test]
+    fn test_despawn_matching() {
+        use query::Matchers;
+
+        struct A;
+        struct Dead;
+
+        let mut world = World::new();
+        let alive = world.create_entity();
+        world.add_component(alive, A);
+        let dead = world.create_entity();
+        world.add_component(dead, A);
+        world.add_component(dead, Dead);
+        let bare_dead = world.create_entity();
+        world.add_component(bare_dead, Dead);
+        let bare = world.create_entity();
+
+        assert_eq!(world.despawn_matching(Matchers::with::<Dead>()), 2);
+        assert_eq!(world.valid_ents[alive].get(), true);
+        assert_eq!(world.valid_ents[dead].get(), false);
+        assert_eq!(world.valid_ents[bare_dead].get(), false);
+        assert_eq!(world.valid_ents[bare].get(), true);
+
+        assert_eq!(world.despawn_matching(Matchers::with::<Dead>()), 0);
+    }
+
+    #[test]
+    fn test_despawn_if() {
+        struct Health(u32);
+
+        let mut world = World::new();
+        let healthy = world.create_entity();
+        world.add_component(healthy, Health(10));
+        let dead = world.create_entity();
+        world.add_component(dead, Health(0));
+        let bare = world.create_entity();
+
+        let is_dead = |ent, world: &World| {
+            world.get_component_ref::<Health>(ent).is_some_and(|health| health.0 == 0)
+        };
+        assert_eq!(world.despawn_if(is_dead), 1);
+        assert_eq!(world.valid_ents[healthy].get(), true);
+        assert_eq!(world.valid_ents[dead].get(), false);
+        assert_eq!(world.valid_ents[bare].get(), true);
+
+        assert_eq!(world.despawn_if(is_dead), 0);
+    }
+
+    #[test]
+    fn test_find_first() {
+        use query::Matchers;
+
+        struct Player;
+        struct Enemy;
+
+        let mut world = World::new();
+        assert_eq!(world.find_first(Matchers::with::<Player>()), None);
+        assert_eq!(world.any(Matchers::with::<Player>()), false);
+        assert_eq!(world.any(Matchers::any()), false);
+
+        world.create_entity();
+        let first = world.create_entity();
+        world.add_component(first, Enemy);
+        let player = world.create_entity();
+        world.add_component(player, Player);
+        let second = world.create_entity();
+        world.add_component(second, Enemy);
+
+        assert_eq!(world.find_first(Matchers::with::<Enemy>()), Some(first));
+        assert_eq!(world.find_first(Matchers::with::<Player>()), Some(player));
+        assert_eq!(world.any(Matchers::with::<Player>()), true);
+
+        world.drop_entity(player);
+        world.drop_entity(first);
+        assert_eq!(world.find_first(Matchers::with::<Enemy>()), Some(second));
+        assert_eq!(world.any(Matchers::with::<Player>()), false);
     }
 }
\ No newline at end of file
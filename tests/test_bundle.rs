@@ -0,0 +1,54 @@
+extern crate apollo_ecs;
+
+use apollo_ecs::*;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+struct Position(f32, f32);
+#[derive(Debug, PartialEq)]
+struct Velocity(f32, f32);
+struct Health(u32);
+
+#[derive(Bundle)]
+struct PlayerBundle {
+    pos: Position,
+    pub vel: Velocity,
+    pub(crate) health: Health,
+    #[bundle(skip)]
+    #[allow(dead_code)]
+    label: &'static str
+}
+
+#[derive(Bundle)]
+struct Tagged {
+    handlers: HashMap<u32, fn(u32) -> u32>,
+    pos: Position
+}
+
+#[test]
+fn test_spawn_bundle() {
+    let mut world = World::new();
+    let player = world.spawn_bundle(PlayerBundle {
+        pos: Position(1.0, 2.0),
+        vel: Velocity(0.5, 0.0),
+        health: Health(100),
+        label: "player"
+    });
+
+    assert_eq!(*world.get_component_ref::<Position>(player).unwrap(), Position(1.0, 2.0));
+    assert_eq!(*world.get_component_ref::<Velocity>(player).unwrap(), Velocity(0.5, 0.0));
+    assert_eq!(world.get_component_ref::<Health>(player).unwrap().0, 100);
+    assert!(!world.has_component::<&'static str>(player));
+}
+
+#[test]
+fn test_bundle_generic_field_types() {
+    let mut world = World::new();
+    let ent = world.spawn_bundle(Tagged {
+        handlers: HashMap::new(),
+        pos: Position(0.0, 0.0)
+    });
+
+    assert_eq!(world.components_of(ent).len(), 2);
+    assert!(world.has_component::<Position>(ent));
+}
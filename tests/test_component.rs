@@ -0,0 +1,39 @@
+extern crate apollo_ecs;
+
+use apollo_ecs::*;
+use apollo_ecs::systems::IterativeSystem;
+
+struct Position(f32);
+
+struct Frozen;
+
+struct MoveSystem;
+
+impl IterativeSystem for MoveSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Position>().without::<Frozen>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        world.get_component::<Position>(ent).unwrap().0 += 1.0;
+    }
+}
+
+#[test]
+fn test_component_storage() {
+    let mut world = World::new();
+    world.register_iterative_system(MoveSystem);
+
+    let moving = world.create_entity();
+    world.add_component(moving, Position(0.0));
+    let frozen = world.create_entity();
+    world.add_component(frozen, Position(0.0));
+    world.add_component(frozen, Frozen);
+
+    world.process();
+
+    assert_eq!(world.get_component_ref::<Position>(moving).unwrap().0, 1.0);
+    assert_eq!(world.get_component_ref::<Position>(frozen).unwrap().0, 0.0);
+    assert_eq!(Frozen::STORAGE, StorageKind::Tag);
+    assert_eq!(Position::STORAGE, StorageKind::Boxed);
+}
@@ -0,0 +1,42 @@
+#![cfg(feature = "ffi")]
+
+extern crate apollo_ecs;
+
+use std::ptr;
+use std::slice;
+
+use apollo_ecs::ffi::*;
+
+const POSITION: u64 = 0x1234;
+const VELOCITY: u64 = 0x5678;
+
+#[test]
+fn test_blob_round_trip() {
+    unsafe {
+        let world = apollo_world_new();
+        let ent = apollo_world_create_entity(world);
+
+        let position = [1u8, 2, 3, 4];
+        assert!(apollo_world_add_component_raw(world, ent, POSITION, position.as_ptr(), position.len()));
+        assert!(apollo_world_add_component_raw(world, ent, VELOCITY, ptr::null(), 0));
+        apollo_world_process(world);
+
+        let mut size = 0;
+        let data = apollo_world_get_component_raw(world, ent, POSITION, &mut size);
+        assert_eq!(slice::from_raw_parts(data, size), &position);
+
+        // Modified in place
+        *data = 10;
+        let data = apollo_world_get_component_raw(world, ent, POSITION, ptr::null_mut());
+        assert_eq!(*data, 10);
+
+        assert!(apollo_world_remove_component_raw(world, ent, POSITION));
+        assert!(apollo_world_get_component_raw(world, ent, POSITION, &mut size).is_null());
+        assert!(!apollo_world_get_component_raw(world, ent, VELOCITY, &mut size).is_null());
+        assert_eq!(size, 0);
+
+        apollo_world_remove_entity(world, ent);
+        assert!(!apollo_world_add_component_raw(world, ent, POSITION, position.as_ptr(), position.len()));
+        apollo_world_free(world);
+    }
+}
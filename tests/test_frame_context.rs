@@ -0,0 +1,46 @@
+extern crate apollo_ecs;
+
+use apollo_ecs::{Entity, EntityQuery, World, Matchers, FrameContext};
+use apollo_ecs::systems::IterativeSystem;
+
+struct Clock {
+    elapsed: f32
+}
+
+struct TimerSystem;
+
+impl IterativeSystem for TimerSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Clock>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        let ctx = world.get_resource::<FrameContext>().unwrap();
+        let mut clock = world.get_component::<Clock>(ent).unwrap();
+
+        clock.elapsed += ctx.dt;
+    }
+}
+
+#[test]
+fn test_frame_context() {
+    let mut world = World::new();
+    world.register_iterative_system(TimerSystem);
+
+    let ent = world.create_entity();
+    world.add_component(ent, Clock { elapsed: 0.0 });
+
+    world.process_dt(0.5);
+    world.process_dt(0.25);
+    world.process_dt(0.25);
+
+    assert_eq!(world.get_component::<Clock>(ent).unwrap().elapsed, 1.0);
+
+    let ctx = world.get_resource::<FrameContext>().unwrap();
+    assert_eq!(ctx.frame, 3);
+    assert_eq!(ctx.dt, 0.25);
+
+    world.process();
+    assert_eq!(world.get_resource::<FrameContext>().unwrap().frame, 4);
+    assert_eq!(world.get_component::<Clock>(ent).unwrap().elapsed, 1.0);
+}
@@ -47,7 +47,6 @@ fn test_iterative_system() {
 
     world.process();
 
-    unsafe {
-        assert_eq!(MATCHED, 3);
-    }
+    let matched = unsafe { MATCHED };
+    assert_eq!(matched, 3);
 }
\ No newline at end of file
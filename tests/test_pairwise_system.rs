@@ -0,0 +1,45 @@
+extern crate apollo_ecs;
+
+use apollo_ecs::{Entity, EntityQuery, World, Matchers};
+use apollo_ecs::systems::PairwiseSystem;
+
+struct CollisionSystem;
+
+struct Collider;
+struct Static;
+
+static mut VISITED: usize = 0;
+
+impl PairwiseSystem for CollisionSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Collider>().without::<Static>())
+    }
+
+    fn process(&mut self, a: Entity, b: Entity, _world: &World) {
+        assert!(a < b);
+        unsafe {
+            VISITED += 1;
+        }
+    }
+}
+
+#[test]
+fn test_pairwise_system() {
+    let mut world = World::new();
+    world.register_pairwise_system(CollisionSystem);
+
+    let n = 7;
+    for _ in 0..n {
+        let ent = world.create_entity();
+        world.add_component(ent, Collider);
+    }
+
+    let ent = world.create_entity();
+    world.add_component(ent, Collider);
+    world.add_component(ent, Static);
+
+    world.process();
+
+    let visited = unsafe { VISITED };
+    assert_eq!(visited, n * (n - 1) / 2);
+}
@@ -0,0 +1,194 @@
+#![cfg(feature = "parallel")]
+
+extern crate apollo_ecs;
+
+use std::any::TypeId;
+
+use apollo_ecs::{Entity, EntityQuery, World, Matchers};
+use apollo_ecs::systems::{IterativeSystem, ParallelSystem};
+
+struct Position(f32);
+struct Velocity(f32);
+struct Health(i32);
+
+struct MoveSystem;
+struct HealSystem;
+struct ClampSystem;
+struct DriftSystem;
+struct SneakySystem;
+struct ReapSystem;
+
+impl IterativeSystem for MoveSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Position>().with::<Velocity>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        let vel = world.get_component_ref::<Velocity>(ent).unwrap().0;
+        world.get_component::<Position>(ent).unwrap().0 += vel;
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec!(TypeId::of::<Velocity>())
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Position>()))
+    }
+}
+
+impl IterativeSystem for HealSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Health>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        world.get_component::<Health>(ent).unwrap().0 += 1;
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Health>()))
+    }
+}
+
+// Declares nothing, so always runs on its own after the batch above
+impl IterativeSystem for ClampSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Health>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        let mut health = world.get_component::<Health>(ent).unwrap();
+        if health.0 > 10 {
+            health.0 = 10;
+        }
+    }
+}
+
+impl ParallelSystem for DriftSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Position>().with::<Velocity>())
+    }
+
+    fn process(&self, ent: Entity, world: &World) {
+        let vel = world.get_component_ref::<Velocity>(ent).unwrap().0;
+        world.get_component::<Position>(ent).unwrap().0 -= vel;
+    }
+
+    fn reads() -> Vec<TypeId> {
+        vec!(TypeId::of::<Velocity>())
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Position>()))
+    }
+}
+
+// Reads `Velocity` without declaring it
+impl ParallelSystem for SneakySystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Position>())
+    }
+
+    fn process(&self, ent: Entity, world: &World) {
+        world.get_component_ref::<Velocity>(ent);
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Position>()))
+    }
+}
+
+impl ParallelSystem for ReapSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Health>())
+    }
+
+    fn process(&self, ent: Entity, world: &World) {
+        world.remove_entity(ent);
+    }
+
+    fn writes() -> Option<Vec<TypeId>> {
+        Some(vec!(TypeId::of::<Health>()))
+    }
+}
+
+fn populate(world: &mut World) -> Vec<Entity> {
+    let mut ents = Vec::new();
+    for i in 0..1000 {
+        let ent = world.create_entity();
+        world.add_component(ent, Position(0.0));
+        world.add_component(ent, Velocity(i as f32));
+        world.add_component(ent, Health(0));
+        ents.push(ent);
+    }
+
+    ents
+}
+
+#[test]
+fn test_process_parallel() {
+    let mut world = World::new();
+    world.register_iterative_system(MoveSystem);
+    world.register_iterative_system(HealSystem);
+    world.register_iterative_system(ClampSystem);
+
+    let ents = populate(&mut world);
+
+    for _ in 0..20 {
+        unsafe {
+            world.process_parallel();
+        }
+    }
+
+    for (i, &ent) in ents.iter().enumerate() {
+        assert_eq!(world.get_component::<Position>(ent).unwrap().0, i as f32 * 20.0);
+        assert_eq!(world.get_component::<Health>(ent).unwrap().0, 10);
+    }
+}
+
+#[test]
+fn test_parallel_system() {
+    let mut world = World::new();
+    world.register_parallel_system(DriftSystem);
+    world.register_iterative_system(HealSystem);
+    let ents = populate(&mut world);
+
+    for _ in 0..20 {
+        unsafe {
+            world.process_parallel();
+        }
+    }
+    world.process();
+
+    for (i, &ent) in ents.iter().enumerate() {
+        assert_eq!(world.get_component::<Position>(ent).unwrap().0, i as f32 * -21.0);
+        assert_eq!(world.get_component::<Health>(ent).unwrap().0, 21);
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "without declaring it")]
+fn test_undeclared_access() {
+    let mut world = World::new();
+    world.register_parallel_system(SneakySystem);
+    populate(&mut world);
+
+    unsafe {
+        world.process_parallel();
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "can't add or remove")]
+fn test_structural_change() {
+    let mut world = World::new();
+    world.register_parallel_system(ReapSystem);
+    populate(&mut world);
+
+    unsafe {
+        world.process_parallel();
+    }
+}
@@ -0,0 +1,53 @@
+extern crate apollo_ecs;
+
+use apollo_ecs::{Entity, EntityQuery, World, Matchers};
+use apollo_ecs::systems::IterativeSystem;
+
+struct SetupSystem;
+
+struct A;
+struct Ready;
+
+static mut RUNS: i32 = 0;
+
+impl IterativeSystem for SetupSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<A>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        world.add_component(ent, Ready);
+
+        unsafe {
+            RUNS += 1;
+        }
+    }
+}
+
+#[test]
+fn test_startup_system() {
+    let mut world = World::new();
+    world.register_startup_system(SetupSystem);
+
+    let mut ents = Vec::new();
+    for _ in 0..3 {
+        let ent = world.create_entity();
+        world.add_component(ent, A);
+        ents.push(ent);
+    }
+
+    world.process();
+    let runs = unsafe { RUNS };
+    assert_eq!(runs, 3);
+    for &ent in ents.iter() {
+        assert!(world.has_component::<Ready>(ent));
+    }
+
+    let ent = world.create_entity();
+    world.add_component(ent, A);
+
+    world.process();
+    let runs = unsafe { RUNS };
+    assert_eq!(runs, 3);
+    assert!(!world.has_component::<Ready>(ent));
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "sync")]
+
+extern crate apollo_ecs;
+
+use std::sync::Arc;
+use std::thread;
+
+use apollo_ecs::{Entity, EntityQuery, World, SyncWorld, Matchers};
+use apollo_ecs::systems::IterativeSystem;
+
+struct Counter(u32);
+
+struct CountSystem;
+
+impl IterativeSystem for CountSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Counter>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        world.get_component::<Counter>(ent).unwrap().0 += 1;
+    }
+}
+
+#[test]
+fn test_process_from_threads() {
+    let mut world = World::new();
+    world.register_iterative_system(CountSystem);
+    let ent = world.create_entity();
+    world.add_component(ent, Counter(0));
+
+    let world = Arc::new(unsafe { SyncWorld::new(world) });
+    let workers: Vec<_> = (0..4).map(|_| {
+        let world = world.clone();
+        thread::spawn(move || {
+            for _ in 0..10 {
+                world.lock().process();
+            }
+        })
+    }).collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    let world = Arc::try_unwrap(world).ok().unwrap().into_inner();
+    assert_eq!(world.get_component_ref::<Counter>(ent).unwrap().0, 40);
+}
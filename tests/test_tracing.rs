@@ -0,0 +1,97 @@
+#![cfg(feature = "tracing")]
+
+extern crate apollo_ecs;
+extern crate tracing;
+
+use std::any;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::{Event, Metadata, Subscriber};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+
+use apollo_ecs::{Entity, EntityQuery, World, Matchers};
+use apollo_ecs::systems::IterativeSystem;
+
+struct Counter(u32);
+
+struct CountSystem;
+
+impl IterativeSystem for CountSystem {
+    fn get_query() -> EntityQuery {
+        EntityQuery::new(Matchers::with::<Counter>())
+    }
+
+    fn process(&mut self, ent: Entity, world: &World) {
+        world.get_component::<Counter>(ent).unwrap().0 += 1;
+    }
+}
+
+/// Collects the fields of a span or event as `name=value` strings
+struct Fields(Vec<String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push(format!("{}={}", field.name(), value));
+    }
+}
+
+/// Logs every span created and event emitted, with their fields
+#[derive(Default)]
+struct Recorder {
+    next_id: AtomicU64,
+    log: Arc<Mutex<Vec<String>>>
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields(Vec::new());
+        span.record(&mut fields);
+        self.log.lock().unwrap().push(format!("span {} {}", span.metadata().name(), fields.0.join(" ")));
+
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields(Vec::new());
+        event.record(&mut fields);
+        self.log.lock().unwrap().push(format!("event {}", fields.0.join(" ")));
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn test_system_spans() {
+    let mut world = World::new();
+    world.register_iterative_system(CountSystem);
+    for _ in 0..3 {
+        let ent = world.create_entity();
+        world.add_component(ent, Counter(0));
+    }
+
+    let recorder = Recorder::default();
+    let log = recorder.log.clone();
+    tracing::subscriber::with_default(recorder, || world.process());
+
+    assert_eq!(*log.lock().unwrap(), vec!(
+        format!("span system name={}", any::type_name::<CountSystem>()),
+        String::from("event message=system ran matched=3")
+    ));
+}